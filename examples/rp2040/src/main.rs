@@ -2,7 +2,7 @@
 //!
 //! Simulates a microphone that emits a 1 kHz tone and a dummy audio output and
 //! prints the payload length of each thousand received audio frame an reports
-//! changes of the alternate settings.
+//! changes of the alternate settings and of the output sampling rate.
 //!
 #![no_std]
 #![no_main]
@@ -119,19 +119,19 @@ fn main() -> ! {
         -12539, -16383, -19947, -23169, -25995, -28377, -30272, -31650, -32486, -32767, -32486,
         -31650, -30272, -28377, -25995, -23169, -19947, -16383, -12539, -8480, -4276,
     ];
-    let sinetab_le = unsafe { &*(&sinetab as *const _ as *const [u8; 96]) };
+    let sinetab = sinetab.map(|sample| [sample]);
 
     let mut ctr = 0;
     let mut input_alt_setting = 0;
     let mut output_alt_setting = 0;
     loop {
         if usb_dev.poll(&mut [&mut usb_audio]) {
-            let mut buf = [0u8; 1024];
-            if let Ok(len) = usb_audio.read(&mut buf) {
+            let mut frames = [[0i32; 2]; 97];
+            if let Ok(len) = usb_audio.read_frames(&mut frames) {
                 ctr += 1;
                 if ctr >= 1000 {
                     ctr = 0;
-                    writeln!(uart, "RX len = {}", len).unwrap();
+                    writeln!(uart, "RX frames = {}", len).unwrap();
                 }
             }
         }
@@ -147,6 +147,14 @@ fn main() -> ! {
             )
             .unwrap();
         }
-        usb_audio.write(sinetab_le).ok();
+        if usb_audio.output_sample_rate_changed().unwrap() {
+            writeln!(
+                uart,
+                "Output sampling rate {}",
+                usb_audio.output_sample_rate().unwrap()
+            )
+            .unwrap();
+        }
+        usb_audio.write_frames(&sinetab).ok();
     }
 }
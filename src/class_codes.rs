@@ -1,7 +1,7 @@
 //! Audio Device Class Codes as defined in Universal Serial Bus Device Class
 //! Definition for Audio Devices, Release 1.0, Appendix A and Universal Serial
 //! Bus Device Class Definition for Audio Data Formats, Release 1.0, Appendix
-//! A.1.1 (Audio Data Format Type I Codes)
+//! A.1 (Audio Data Format Codes) and A.2 (Format Type Codes)
 //!
 #![allow(dead_code)]
 
@@ -136,6 +136,8 @@ pub const PITCH_CONTROL: u16 = 0x02;
 // Format Type Codes
 pub const FORMAT_TYPE_UNDEFINED: u8 = 0;
 pub const FORMAT_TYPE_I: u8 = 0x01;
+pub const FORMAT_TYPE_II: u8 = 0x02;
+pub const FORMAT_TYPE_III: u8 = 0x03;
 
 // Audio Data Format Type I Codes
 pub const TYPE_I_UNDEFINED: u16 = 0x0000;
@@ -144,3 +146,18 @@ pub const PCM8: u16 = 0x0002;
 pub const IEEE_FLOAT: u16 = 0x0003;
 pub const ALAW: u16 = 0x0004;
 pub const MULAW: u16 = 0x0005;
+
+// Audio Data Format Type II Codes
+pub const TYPE_II_UNDEFINED: u16 = 0x1000;
+pub const MPEG: u16 = 0x1001;
+pub const AC_3: u16 = 0x1002;
+
+// Audio Data Format Type III Codes
+pub const TYPE_III_UNDEFINED: u16 = 0x2000;
+pub const IEC1937_AC_3: u16 = 0x2001;
+pub const IEC1937_MPEG_1_LAYER1: u16 = 0x2002;
+pub const IEC1937_MPEG_1_LAYER2_3: u16 = 0x2003;
+pub const IEC1937_MPEG_2_NOEXT: u16 = 0x2003;
+pub const IEC1937_MPEG_2_EXT: u16 = 0x2004;
+pub const IEC1937_MPEG_2_LAYER1_LS: u16 = 0x2005;
+pub const IEC1937_MPEG_2_LAYER2_3_LS: u16 = 0x2006;
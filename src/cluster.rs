@@ -0,0 +1,91 @@
+//! Logical channel clusters and their spatial locations
+//!
+
+/// Spatial location of a predefined channel of a cluster. The channels of a
+/// cluster are ordered by their spatial locations in the order of this
+/// enumeration followed by the non-predefined channels.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum SpatialLocation {
+    /// Left Front (L)
+    LeftFront,
+    /// Right Front (R)
+    RightFront,
+    /// Center Front (C)
+    CenterFront,
+    /// Low Frequency Enhancement (LFE)
+    LowFrequencyEnhancement,
+    /// Left Surround (Ls)
+    LeftSurround,
+    /// Right Surround (Rs)
+    RightSurround,
+    /// Left of Center (Lc)
+    LeftOfCenter,
+    /// Right of Center (Rc)
+    RightOfCenter,
+    /// Surround (S)
+    Surround,
+    /// Side Left (Sl)
+    SideLeft,
+    /// Side Right (Sr)
+    SideRight,
+    /// Top (T)
+    Top,
+}
+
+impl SpatialLocation {
+    /// Bit of the spatial location in the wChannelConfig field
+    pub(crate) fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// Logical channel cluster at the output of a Terminal or Unit
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Cluster {
+    /// Number of channels (bNrChannels)
+    pub(crate) channels: u8,
+    /// Spatial locations of the predefined channels (wChannelConfig)
+    pub(crate) config: u16,
+    /// String index of the name of the first non-predefined channel
+    /// (iChannelNames)
+    pub(crate) names: u8,
+}
+
+impl Cluster {
+    /// Create a cluster of `channels` channels with the spatial locations
+    /// commonly used for this number of channels. The channels of other
+    /// clusters are non-predefined.
+    pub(crate) fn new(channels: u8) -> Cluster {
+        let config = match channels {
+            1 => 0x0001, // L
+            2 => 0x0003, // L+R
+            4 => 0x0033, // L+R+LS+RS
+            6 => 0x003F, // L+R+C+LFE+LS+RS
+            8 => 0x00FF, // L+R+C+LFE+LS+RS+LC+RC
+            _ => 0x0000, // non-predefined channels
+        };
+        Cluster {
+            channels,
+            config,
+            names: 0,
+        }
+    }
+
+    /// Cluster of the output of a Unit having `channels` output channels and
+    /// this cluster at Input Pin 1. The cluster is retained if the number of
+    /// channels does not change.
+    pub(crate) fn output(self, channels: u8) -> Cluster {
+        if channels == self.channels {
+            self
+        } else {
+            Cluster::new(channels)
+        }
+    }
+
+    /// bNrChannels, wChannelConfig and iChannelNames fields of a Terminal or
+    /// Unit Descriptor
+    pub(crate) fn descriptor(&self) -> [u8; 4] {
+        let [config_lo, config_hi] = self.config.to_le_bytes();
+        [self.channels, config_lo, config_hi, self.names]
+    }
+}
@@ -0,0 +1,88 @@
+//! Conversion between linear 16 bit samples and the 8 bit sample formats
+//! `Format::U8`, `Format::ALaw` and `Format::MuLaw`
+//!
+//! The A-law and µ-law conversions follow ITU-T Recommendation G.711.
+//!
+
+/// Bias added to the magnitude of a sample before µ-law encoding
+const MULAW_BIAS: i32 = 0x84;
+
+/// Maximum magnitude of a sample that can be µ-law encoded
+const MULAW_CLIP: i32 = 32635;
+
+/// Encode a linear sample as unsigned 8 bit sample
+pub fn u8_encode(sample: i16) -> u8 {
+    ((sample >> 8) as u8) ^ 0x80
+}
+
+/// Decode an unsigned 8 bit sample to a linear sample
+pub fn u8_decode(sample: u8) -> i16 {
+    ((sample ^ 0x80) as i8 as i16) << 8
+}
+
+/// Encode a linear sample as A-law sample
+pub fn alaw_encode(sample: i16) -> u8 {
+    let mut pcm = sample as i32 >> 3;
+    let mask = if pcm >= 0 {
+        0xd5
+    } else {
+        pcm = -pcm - 1;
+        0x55
+    };
+    // segment end points are 0x1f, 0x3f, 0x7f, ..., 0xfff
+    let segment = match (32 - (pcm as u32 >> 5).leading_zeros()) as i32 {
+        segment if segment > 7 => return 0x7f ^ mask,
+        segment => segment,
+    };
+    let mantissa = if segment < 2 {
+        (pcm >> 1) & 0x0f
+    } else {
+        (pcm >> segment) & 0x0f
+    };
+    ((segment << 4) | mantissa) as u8 ^ mask
+}
+
+/// Decode an A-law sample to a linear sample
+pub fn alaw_decode(sample: u8) -> i16 {
+    let sample = sample ^ 0x55;
+    let segment = (sample >> 4) & 0x07;
+    let mut magnitude = ((sample & 0x0f) as i16) << 4;
+    match segment {
+        0 => magnitude += 8,
+        1 => magnitude += 0x108,
+        _ => magnitude = (magnitude + 0x108) << (segment - 1),
+    }
+    if sample & 0x80 != 0 {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+/// Encode a linear sample as µ-law sample
+pub fn mulaw_encode(sample: i16) -> u8 {
+    let mut pcm = sample as i32;
+    let sign = if pcm < 0 {
+        pcm = -pcm;
+        0x80
+    } else {
+        0x00
+    };
+    pcm = pcm.min(MULAW_CLIP) + MULAW_BIAS;
+    let exponent = 24 - (pcm as u32).leading_zeros() as i32;
+    let mantissa = (pcm >> (exponent + 3)) & 0x0f;
+    !((sign | (exponent << 4) | mantissa) as u8)
+}
+
+/// Decode a µ-law sample to a linear sample
+pub fn mulaw_decode(sample: u8) -> i16 {
+    let sample = !sample;
+    let exponent = (sample >> 4) & 0x07;
+    let mantissa = (sample & 0x0f) as i32;
+    let magnitude = (((mantissa << 3) + MULAW_BIAS) << exponent) - MULAW_BIAS;
+    if sample & 0x80 != 0 {
+        -magnitude as i16
+    } else {
+        magnitude as i16
+    }
+}
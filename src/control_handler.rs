@@ -0,0 +1,126 @@
+//! Dispatching of class-specific requests to a user-defined handler
+//!
+
+use crate::class_codes::*;
+use usb_device::control::Request;
+use usb_device::endpoint::EndpointAddress;
+use usb_device::UsbDirection;
+
+/// Attribute of a control addressed by a class-specific request
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Attribute {
+    /// Current setting (SET_CUR, GET_CUR)
+    Cur,
+    /// Minimum setting (SET_MIN, GET_MIN)
+    Min,
+    /// Maximum setting (SET_MAX, GET_MAX)
+    Max,
+    /// Resolution (SET_RES, GET_RES)
+    Res,
+    /// Memory space (SET_MEM, GET_MEM)
+    Mem,
+    /// Status (GET_STAT)
+    Stat,
+}
+
+impl Attribute {
+    /// Attribute of a request code
+    fn from_request(request: u8) -> Option<Attribute> {
+        match request {
+            SET_CUR | GET_CUR => Some(Attribute::Cur),
+            SET_MIN | GET_MIN => Some(Attribute::Min),
+            SET_MAX | GET_MAX => Some(Attribute::Max),
+            SET_RES | GET_RES => Some(Attribute::Res),
+            SET_MEM | GET_MEM => Some(Attribute::Mem),
+            GET_STAT => Some(Attribute::Stat),
+            _ => None,
+        }
+    }
+}
+
+/// Recipient of a class-specific request
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ControlTarget {
+    /// Terminal or Unit of the AudioControl interface identified by its ID
+    Entity(u8),
+    /// Isochronous endpoint of a stream
+    Endpoint(EndpointAddress),
+}
+
+/// Class-specific request decoded from the setup packet
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ControlRequest {
+    /// Terminal, Unit or endpoint addressed by the request
+    pub target: ControlTarget,
+    /// Attribute of the control
+    pub attribute: Attribute,
+    /// Control selector (CS) or 0 for MEM and GET_STAT requests
+    pub selector: u8,
+    /// Channel number (CN) or 0 for MEM and GET_STAT requests. For Mixer
+    /// Units, `selector` is the input channel number and `channel` is the
+    /// output channel number.
+    pub channel: u8,
+    /// Offset within the memory space for MEM requests, 0 otherwise
+    pub offset: u16,
+}
+
+impl ControlRequest {
+    /// Decode a class-specific request addressed to `target`. Returns `None`
+    /// if the request code is unknown or does not match the direction of the
+    /// data stage.
+    pub(crate) fn new(req: &Request, target: ControlTarget) -> Option<ControlRequest> {
+        let attribute = Attribute::from_request(req.request)?;
+        if (req.request & 0x80 != 0) != (req.direction == UsbDirection::In) {
+            return None;
+        }
+        let (selector, channel, offset) = match attribute {
+            Attribute::Mem => (0, 0, req.value),
+            Attribute::Stat => (0, 0, 0),
+            _ => ((req.value >> 8) as u8, req.value as u8, 0),
+        };
+        Some(ControlRequest {
+            target,
+            attribute,
+            selector,
+            channel,
+            offset,
+        })
+    }
+}
+
+/// Handler of the class-specific requests not handled by the `AudioClass`
+/// itself.
+///
+/// The `AudioClass` handles the controls of Feature Units, Selector Units,
+/// Mixer Units and Processing Units as well as the Sampling Frequency Control
+/// of the endpoints. All other requests addressed to an existing Terminal or
+/// Unit or to the endpoint of a stream are passed to the handler, e.g. the
+/// controls of Extension Units, Copy Protect Controls of Terminals and MEM
+/// requests. GET_STAT requests are passed to the handler unless the status
+/// endpoint is configured, in which case the `AudioClass` answers them with
+/// the status of its interrupts, see `AudioClass::notify_control_changed`.
+/// Requests to controls of an Extension Unit that are not indicated in its
+/// bmControls field are rejected before reaching the handler.
+///
+/// The handler is passed to `AudioClassBuilder::build_with_handler` and can be
+/// accessed by means of `AudioClass::handler` and `AudioClass::handler_mut`.
+/// Rejected requests are answered with a STALL. The default implementations
+/// reject all requests.
+pub trait AudioControlHandler {
+    /// Handle a GET request. `data` can hold up to `MAX_CONTROL_SIZE` bytes.
+    /// Returns the number of bytes written to `data` or `None` to reject the
+    /// request. The response is truncated to the length requested by the
+    /// host.
+    fn get(&mut self, _request: &ControlRequest, _data: &mut [u8]) -> Option<usize> {
+        None
+    }
+
+    /// Handle a SET request with the parameter block `data`. Returns `false`
+    /// to reject the request.
+    fn set(&mut self, _request: &ControlRequest, _data: &[u8]) -> bool {
+        false
+    }
+}
+
+/// Handler rejecting all requests
+impl AudioControlHandler for () {}
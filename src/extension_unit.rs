@@ -0,0 +1,140 @@
+//! Extension Unit with vendor specific controls handled by the firmware
+//!
+
+use crate::class_codes::*;
+use crate::cluster::Cluster;
+use crate::control_handler::{Attribute, ControlRequest};
+use crate::topology::AcDescriptor;
+use crate::MixerSource;
+
+/// Maximum number of Input Pins of an Extension Unit
+pub(crate) const MAX_INPUT_PINS: usize = 4;
+
+/// Configuration of an Extension Unit placed in front of the Feature Unit or
+/// the Output Terminal of a stream or added to a `Topology`.
+///
+/// The Extension Unit of a stream processes the signal of the stream (Input Pin 1) and
+/// optionally the signals of other streams (Input Pins 2, 3, ...). Its output
+/// has the channels of the stream. The processing is vendor specific and
+/// identified by the extension code.
+#[derive(Clone, Copy, Debug)]
+pub struct ExtensionUnitConfig<'a> {
+    extension_code: u16,
+    controls: u16,
+    sources: &'a [MixerSource],
+}
+
+impl<'a> ExtensionUnitConfig<'a> {
+    /// Create an Extension Unit configuration with the vendor specific
+    /// extension code `extension_code` having the Enable Processing Control
+    /// (`XU_ENABLE_CONTROL`) only.
+    pub fn new(extension_code: u16) -> ExtensionUnitConfig<'a> {
+        ExtensionUnitConfig {
+            extension_code,
+            controls: 0x0001,
+            sources: &[],
+        }
+    }
+
+    /// Set the controls of the Extension Unit. Bit n - 1 of the bmControls
+    /// bitmap `controls` indicates that control n is available, e.g. bit 0
+    /// corresponds to the Enable Processing Control.
+    pub fn controls(self, controls: u16) -> ExtensionUnitConfig<'a> {
+        ExtensionUnitConfig { controls, ..self }
+    }
+
+    /// Connect up to 3 further Input Pins to the signals of the streams
+    /// `sources`. The sources must not lead to a cycle. A configuration
+    /// added to a `Topology` has no sources.
+    pub fn sources(self, sources: &'a [MixerSource]) -> ExtensionUnitConfig<'a> {
+        ExtensionUnitConfig { sources, ..self }
+    }
+
+    /// Streams connected to the further Input Pins
+    pub(crate) fn source_streams(&self) -> &'a [MixerSource] {
+        self.sources
+    }
+}
+
+/// Extension Unit state
+pub(crate) struct ExtensionUnit {
+    pub(crate) id: u8,
+    extension_code: u16,
+    controls: u16,
+    num_pins: u8,
+    source_ids: [u8; MAX_INPUT_PINS],
+    cluster: Cluster,
+}
+
+impl ExtensionUnit {
+    /// Create an Extension Unit state. `source_ids` are the IDs of the
+    /// Terminals or Units connected to the Input Pins and `cluster` is the
+    /// output cluster. Returns `None` if the configuration is not valid.
+    pub(crate) fn new(
+        config: &ExtensionUnitConfig,
+        id: u8,
+        source_ids: &[u8],
+        cluster: Cluster,
+    ) -> Option<ExtensionUnit> {
+        if source_ids.is_empty() || source_ids.len() > MAX_INPUT_PINS {
+            return None;
+        }
+        let mut xu = ExtensionUnit {
+            id,
+            extension_code: config.extension_code,
+            controls: config.controls,
+            num_pins: source_ids.len() as u8,
+            source_ids: [0; MAX_INPUT_PINS],
+            cluster,
+        };
+        xu.source_ids[..source_ids.len()].copy_from_slice(source_ids);
+        Some(xu)
+    }
+
+    /// Size of the bmControls field
+    fn control_size(&self) -> usize {
+        if self.controls > 0xff {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Check if a control is indicated in the bmControls field
+    fn has_control(&self, selector: u8) -> bool {
+        (1..=16).contains(&selector) && self.controls & 1 << (selector - 1) != 0
+    }
+
+    /// Check if a request is addressed to an existing control. MEM and
+    /// GET_STAT requests address the Extension Unit as a whole.
+    pub(crate) fn accepts(&self, request: &ControlRequest) -> bool {
+        match request.attribute {
+            Attribute::Mem | Attribute::Stat => true,
+            _ => request.channel == 0 && self.has_control(request.selector),
+        }
+    }
+}
+
+impl AcDescriptor for ExtensionUnit {
+    fn id(&self) -> u8 {
+        self.id
+    }
+
+    fn write_descriptor(&self, desc: &mut [u8], name: u8) -> usize {
+        let num_pins = self.num_pins as usize;
+        let control_size = self.control_size();
+        desc[0] = EXTENSION_UNIT; // bDescriptorSubtype
+        desc[1] = self.id; // bUnitID
+        desc[2..4].copy_from_slice(&self.extension_code.to_le_bytes()); // wExtensionCode
+        desc[4] = self.num_pins; // bNrInPins
+        desc[5..5 + num_pins].copy_from_slice(&self.source_ids[..num_pins]); // baSourceID
+        let mut ndx = 5 + num_pins;
+        desc[ndx..ndx + 4].copy_from_slice(&self.cluster.descriptor()); // bNrChannels, wChannelConfig, iChannelNames
+        desc[ndx + 4] = control_size as u8; // bControlSize
+        ndx += 5;
+        desc[ndx..ndx + control_size].copy_from_slice(&self.controls.to_le_bytes()[..control_size]); // bmControls
+        ndx += control_size;
+        desc[ndx] = name; // iExtension
+        ndx + 1
+    }
+}
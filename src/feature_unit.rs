@@ -0,0 +1,235 @@
+//! Feature Unit with host controlled mute and volume controls
+//!
+
+use crate::class_codes::*;
+use crate::topology::AcDescriptor;
+
+/// Maximum number of logical channels of a stream having a Feature Unit
+pub(crate) const MAX_CHANNELS: usize = 8;
+
+/// bmaControls bit of the Mute Control
+const FU_MUTE: u8 = 0x01;
+/// bmaControls bit of the Volume Control
+const FU_VOLUME: u8 = 0x02;
+
+/// Volume value indicating silence (-infinity dB)
+const VOLUME_SILENCE: i16 = i16::MIN;
+
+/// Configuration of a Feature Unit placed between the Input Terminal and the
+/// Output Terminal of a stream or added to a `Topology`.
+///
+/// Mute and volume controls can be enabled as master controls (channel 0)
+/// affecting all channels and/or as controls of the individual logical
+/// channels. Volume values are indicated in units of 1/256 dB.
+#[derive(Clone, Copy, Debug)]
+pub struct FeatureUnitConfig {
+    master_controls: u8,
+    channel_controls: u8,
+    volume_min: i16,
+    volume_max: i16,
+    volume_res: i16,
+}
+
+impl Default for FeatureUnitConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FeatureUnitConfig {
+    /// Create a Feature Unit configuration without any controls. The volume
+    /// range defaults to -60 dB to 0 dB in steps of 1 dB.
+    pub fn new() -> FeatureUnitConfig {
+        FeatureUnitConfig {
+            master_controls: 0,
+            channel_controls: 0,
+            volume_min: -60 * 256,
+            volume_max: 0,
+            volume_res: 256,
+        }
+    }
+
+    /// Enable the master Mute Control
+    pub fn master_mute(mut self) -> FeatureUnitConfig {
+        self.master_controls |= FU_MUTE;
+        self
+    }
+
+    /// Enable the master Volume Control
+    pub fn master_volume(mut self) -> FeatureUnitConfig {
+        self.master_controls |= FU_VOLUME;
+        self
+    }
+
+    /// Enable a Mute Control for each logical channel
+    pub fn channel_mute(mut self) -> FeatureUnitConfig {
+        self.channel_controls |= FU_MUTE;
+        self
+    }
+
+    /// Enable a Volume Control for each logical channel
+    pub fn channel_volume(mut self) -> FeatureUnitConfig {
+        self.channel_controls |= FU_VOLUME;
+        self
+    }
+
+    /// Set the range of the Volume Controls in units of 1/256 dB. `min` must
+    /// be less than `max` and `res` must be positive.
+    pub fn volume_range(mut self, min: i16, max: i16, res: i16) -> FeatureUnitConfig {
+        self.volume_min = min;
+        self.volume_max = max;
+        self.volume_res = res;
+        self
+    }
+
+    fn controls(&self, channel: u8) -> u8 {
+        if channel == 0 {
+            self.master_controls
+        } else {
+            self.channel_controls
+        }
+    }
+}
+
+/// Feature Unit state including the current values of the controls
+pub(crate) struct FeatureUnit {
+    config: FeatureUnitConfig,
+    pub(crate) id: u8,
+    source_id: u8,
+    channels: u8,
+    mute: [bool; MAX_CHANNELS + 1],
+    volume: [i16; MAX_CHANNELS + 1],
+}
+
+impl FeatureUnit {
+    /// Create a Feature Unit state processing the `channels` channels of the
+    /// Terminal or Unit `source_id`. Returns `None` if the configuration is
+    /// not valid.
+    pub(crate) fn new(
+        config: FeatureUnitConfig,
+        id: u8,
+        source_id: u8,
+        channels: u8,
+    ) -> Option<FeatureUnit> {
+        if channels as usize > MAX_CHANNELS
+            || config.volume_min >= config.volume_max
+            || config.volume_res <= 0
+        {
+            return None;
+        }
+        Some(FeatureUnit {
+            config,
+            id,
+            source_id,
+            channels,
+            mute: [false; MAX_CHANNELS + 1],
+            volume: [config.volume_max; MAX_CHANNELS + 1],
+        })
+    }
+
+    /// Current state of the Mute Control of a channel (0: master channel)
+    pub(crate) fn mute(&self, channel: u8) -> Option<bool> {
+        (channel <= self.channels).then(|| self.mute[channel as usize])
+    }
+
+    /// Current value of the Volume Control of a channel (0: master channel)
+    pub(crate) fn volume(&self, channel: u8) -> Option<i16> {
+        (channel <= self.channels).then(|| self.volume[channel as usize])
+    }
+
+    fn has_control(&self, control: u8, channel: u8) -> bool {
+        let bit = match control {
+            MUTE_CONTROL => FU_MUTE,
+            VOLUME_CONTROL => FU_VOLUME,
+            _ => return false,
+        };
+        channel <= self.channels && self.config.controls(channel) & bit != 0
+    }
+
+    /// Handle a GET request. Returns the number of bytes written to `data` or
+    /// `None` if the request is not supported.
+    pub(crate) fn get(&self, request: u8, value: u16, data: &mut [u8; 2]) -> Option<usize> {
+        let control = (value >> 8) as u8;
+        let channel = value as u8;
+        if !self.has_control(control, channel) {
+            return None;
+        }
+        match (control, request) {
+            (MUTE_CONTROL, GET_CUR) => {
+                data[0] = self.mute[channel as usize] as u8;
+                Some(1)
+            }
+            (VOLUME_CONTROL, GET_CUR | GET_MIN | GET_MAX | GET_RES) => {
+                let volume = match request {
+                    GET_CUR => self.volume[channel as usize],
+                    GET_MIN => self.config.volume_min,
+                    GET_MAX => self.config.volume_max,
+                    _ => self.config.volume_res,
+                };
+                *data = volume.to_le_bytes();
+                Some(2)
+            }
+            _ => None,
+        }
+    }
+
+    /// Handle a SET request. Returns `false` if the request is not supported.
+    pub(crate) fn set(&mut self, request: u8, value: u16, data: &[u8]) -> bool {
+        let control = (value >> 8) as u8;
+        let channel = value as u8;
+        if request != SET_CUR || !self.has_control(control, channel) {
+            return false;
+        }
+        match control {
+            MUTE_CONTROL if !data.is_empty() => self.set_mute(channel, data[0] != 0),
+            VOLUME_CONTROL if data.len() >= 2 => {
+                self.set_volume(channel, i16::from_le_bytes([data[0], data[1]]))
+            }
+            _ => false,
+        }
+    }
+
+    /// Change the state of the Mute Control of a channel (0: master channel).
+    /// Returns `false` if the control does not exist.
+    pub(crate) fn set_mute(&mut self, channel: u8, mute: bool) -> bool {
+        if !self.has_control(MUTE_CONTROL, channel) {
+            return false;
+        }
+        self.mute[channel as usize] = mute;
+        true
+    }
+
+    /// Change the value of the Volume Control of a channel (0: master
+    /// channel). The value is clamped to the configured range. Returns `false`
+    /// if the control does not exist.
+    pub(crate) fn set_volume(&mut self, channel: u8, volume: i16) -> bool {
+        if !self.has_control(VOLUME_CONTROL, channel) {
+            return false;
+        }
+        self.volume[channel as usize] = if volume == VOLUME_SILENCE {
+            volume
+        } else {
+            volume.clamp(self.config.volume_min, self.config.volume_max)
+        };
+        true
+    }
+}
+
+impl AcDescriptor for FeatureUnit {
+    fn id(&self) -> u8 {
+        self.id
+    }
+
+    fn write_descriptor(&self, desc: &mut [u8], name: u8) -> usize {
+        desc[0] = FEATURE_UNIT; // bDescriptorSubtype
+        desc[1] = self.id; // bUnitID
+        desc[2] = self.source_id; // bSourceID
+        desc[3] = 0x01; // bControlSize
+        for ch in 0..=self.channels {
+            desc[4 + ch as usize] = self.config.controls(ch); // bmaControls
+        }
+        let len = 5 + self.channels as usize + 1;
+        desc[len - 1] = name; // iFeature
+        len
+    }
+}
@@ -0,0 +1,246 @@
+//! Estimation of the feedback value of an asynchronous output stream
+//!
+
+use crate::{Error, Result};
+
+/// Number of USB frames per second (full-speed)
+const FRAMES_PER_SECOND: u64 = 1000;
+
+/// Number of USB microframes per second (high-speed)
+const MICROFRAMES_PER_SECOND: u64 = 8000;
+
+/// Format of a feedback value
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FeedbackFormat {
+    /// Samples per frame (1 ms) in 10.14 format (full-speed)
+    FullSpeed,
+    /// Samples per microframe (125 µs) in 16.16 format (high-speed)
+    HighSpeed,
+}
+
+impl FeedbackFormat {
+    /// Number of fractional bits
+    fn frac_bits(self) -> u32 {
+        match self {
+            FeedbackFormat::FullSpeed => 14,
+            FeedbackFormat::HighSpeed => 16,
+        }
+    }
+
+    /// Number of (micro)frames per second
+    fn frames_per_second(self) -> u64 {
+        match self {
+            FeedbackFormat::FullSpeed => FRAMES_PER_SECOND,
+            FeedbackFormat::HighSpeed => MICROFRAMES_PER_SECOND,
+        }
+    }
+}
+
+/// Estimator of the feedback value of an asynchronous output stream.
+///
+/// The firmware counts the cycles of the codec clock, e.g. the master clock
+/// or the word clock, by means of a timer that is captured at every USB Start
+/// Of Frame (SOF) or every n-th SOF. The number of cycles counted between two
+/// captures is passed to `update`. The estimator converts the counts to the
+/// number of samples per (micro)frame and averages them by means of a first
+/// order low-pass filter.
+///
+/// Optionally, the fill level of the buffer between the USB packets and the
+/// codec is passed to `set_fill_level`. The deviation from a target fill
+/// level is added to the feedback value as a proportional correction such
+/// that the fill level stays bounded in spite of measurement errors.
+///
+/// The resulting feedback value is limited to ±1 sample per (micro)frame
+/// around the nominal value and can be passed to
+/// `AudioClass::set_output_stream_feedback` (full-speed format).
+#[derive(Clone, Copy, Debug)]
+pub struct FeedbackEstimator {
+    format: FeedbackFormat,
+    /// Nominal feedback value for the sampling rate
+    nominal: u32,
+    /// Codec clock cycles per sample
+    cycles_per_sample: u32,
+    /// Number of (micro)frames between two counts
+    period: u32,
+    /// Time constant of the low-pass filter as power of two of updates
+    filter_shift: u8,
+    /// Sum of the filter state scaled by 2^filter_shift
+    filter_sum: u64,
+    /// Target fill level and gain of the fill level correction as power of
+    /// two
+    fill_correction: Option<(usize, u8)>,
+    /// Deviation of the fill level from the target fill level in frames
+    fill_error: i64,
+}
+
+impl FeedbackEstimator {
+    /// Create an estimator for a stream of sampling rate `sample_rate` in
+    /// samples/second. The codec clock has `cycles_per_sample` cycles per
+    /// sample, e.g. 256 for a master clock of 256 fs or 1 for the word clock.
+    /// Returns an error if `sample_rate` or `cycles_per_sample` is zero.
+    pub fn new(
+        format: FeedbackFormat,
+        sample_rate: u32,
+        cycles_per_sample: u32,
+    ) -> Result<FeedbackEstimator> {
+        if sample_rate == 0 || cycles_per_sample == 0 {
+            return Err(Error::InvalidValue);
+        }
+        let mut estimator = FeedbackEstimator {
+            format,
+            nominal: 0,
+            cycles_per_sample,
+            period: 1,
+            filter_shift: 4,
+            filter_sum: 0,
+            fill_correction: None,
+            fill_error: 0,
+        };
+        estimator.reset(sample_rate);
+        Ok(estimator)
+    }
+
+    /// Set the number of (micro)frames between two counts passed to
+    /// `update`. Defaults to 1, i.e. one count per SOF.
+    pub fn with_period(self, frames: u32) -> FeedbackEstimator {
+        FeedbackEstimator {
+            period: frames.max(1),
+            ..self
+        }
+    }
+
+    /// Set the time constant of the low-pass filter to 2^`shift` updates.
+    /// Defaults to 2^4 updates. A shift of 0 disables the filter.
+    pub fn with_filter(self, shift: u8) -> FeedbackEstimator {
+        let shift = shift.min(16);
+        let value = self.filter_sum >> self.filter_shift;
+        FeedbackEstimator {
+            filter_shift: shift,
+            filter_sum: value << shift,
+            ..self
+        }
+    }
+
+    /// Enable the fill level correction. A deviation of the fill level from
+    /// `target` frames changes the feedback value by 2^-`gain_shift` samples
+    /// per (micro)frame for each frame of deviation, i.e. the deviation is
+    /// corrected within about 2^`gain_shift` (micro)frames. `gain_shift` is
+    /// limited to 32.
+    pub fn with_fill_correction(self, target: usize, gain_shift: u8) -> FeedbackEstimator {
+        FeedbackEstimator {
+            fill_correction: Some((target, gain_shift.min(32))),
+            ..self
+        }
+    }
+
+    /// Restart the estimation at the nominal value for a sampling rate, e.g.
+    /// when the host changes the sampling rate
+    pub fn reset(&mut self, sample_rate: u32) {
+        let nominal =
+            ((sample_rate as u64) << self.format.frac_bits()) / self.format.frames_per_second();
+        self.nominal = nominal as u32;
+        self.filter_sum = nominal << self.filter_shift;
+        self.fill_error = 0;
+    }
+
+    /// Pass the number of codec clock cycles counted during the last period
+    pub fn update(&mut self, cycles: u32) {
+        let divisor = self.cycles_per_sample as u64 * self.period as u64;
+        let value = ((cycles as u64) << self.format.frac_bits()) / divisor;
+        self.filter_sum = self.filter_sum - (self.filter_sum >> self.filter_shift) + value;
+    }
+
+    /// Pass the current fill level of the buffer in frames. Ignored unless
+    /// the fill level correction is enabled.
+    pub fn set_fill_level(&mut self, fill: usize) {
+        if let Some((target, _)) = self.fill_correction {
+            self.fill_error = target as i64 - fill as i64;
+        }
+    }
+
+    /// Nominal feedback value for the sampling rate
+    pub fn nominal_value(&self) -> u32 {
+        self.nominal
+    }
+
+    /// Current feedback value
+    pub fn value(&self) -> u32 {
+        let frac_bits = self.format.frac_bits();
+        let mut value = (self.filter_sum >> self.filter_shift) as i64;
+        if let Some((_, gain_shift)) = self.fill_correction {
+            value += (self.fill_error << frac_bits) >> gain_shift;
+        }
+        let one = 1i64 << frac_bits;
+        let nominal = self.nominal as i64;
+        value.clamp(nominal - one, nominal + one).max(0) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nominal_value() {
+        let fs = |rate| FeedbackEstimator::new(FeedbackFormat::FullSpeed, rate, 256).unwrap();
+        assert_eq!(fs(48000).nominal_value(), 48 << 14);
+        assert_eq!(fs(48000).value(), 48 << 14);
+        // 44.1 * 2^14 = 722534.4
+        assert_eq!(fs(44100).nominal_value(), 722534);
+        let hs = FeedbackEstimator::new(FeedbackFormat::HighSpeed, 48000, 1).unwrap();
+        assert_eq!(hs.nominal_value(), 6 << 16);
+        assert!(FeedbackEstimator::new(FeedbackFormat::FullSpeed, 0, 256).is_err());
+    }
+
+    #[test]
+    fn filter_converges() {
+        let mut estimator = FeedbackEstimator::new(FeedbackFormat::FullSpeed, 48000, 256)
+            .unwrap()
+            .with_period(2);
+        // 48.05 samples per frame counted over 2 frames
+        let cycles = 2 * 12301;
+        for _ in 0..400 {
+            estimator.update(cycles);
+        }
+        assert_eq!(estimator.value(), 12301 << 6);
+        estimator.reset(48000);
+        assert_eq!(estimator.value(), 48 << 14);
+    }
+
+    #[test]
+    fn fill_correction_sign() {
+        let mut estimator = FeedbackEstimator::new(FeedbackFormat::FullSpeed, 48000, 256)
+            .unwrap()
+            .with_fill_correction(100, 8);
+        let nominal = estimator.nominal_value();
+        // a buffer running empty asks the host for more samples
+        estimator.set_fill_level(90);
+        assert_eq!(estimator.value(), nominal + (10 << 14 >> 8));
+        estimator.set_fill_level(110);
+        assert_eq!(estimator.value(), nominal - (10 << 14 >> 8));
+        estimator.set_fill_level(100);
+        assert_eq!(estimator.value(), nominal);
+    }
+
+    #[test]
+    fn value_is_clamped() {
+        let mut estimator = FeedbackEstimator::new(FeedbackFormat::FullSpeed, 48000, 1)
+            .unwrap()
+            .with_filter(0);
+        let nominal = estimator.nominal_value();
+        estimator.update(96);
+        assert_eq!(estimator.value(), nominal + (1 << 14));
+        estimator.update(0);
+        assert_eq!(estimator.value(), nominal - (1 << 14));
+        let mut estimator = estimator.with_fill_correction(1000, 0);
+        estimator.update(48);
+        estimator.set_fill_level(0);
+        assert_eq!(estimator.value(), nominal + (1 << 14));
+        estimator.set_fill_level(2000);
+        assert_eq!(estimator.value(), nominal - (1 << 14));
+        // large shifts do not overflow
+        let mut estimator = estimator.with_fill_correction(1000, u8::MAX);
+        estimator.set_fill_level(0);
+        assert_eq!(estimator.value(), nominal);
+    }
+}
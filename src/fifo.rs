@@ -0,0 +1,337 @@
+//! Lock-free single-producer single-consumer FIFO of audio frames
+//!
+//! The FIFO decouples the packets of a stream, which arrive or are sent every
+//! USB frame (1 ms), from the buffers of a codec, which are typically
+//! processed in a DMA interrupt. For an output stream, the producer is fed by
+//! `AudioClass::read_fifo` and the consumer drains the FIFO in the DMA
+//! interrupt. For an input stream, the roles are swapped: the producer is fed
+//! in the DMA interrupt and the consumer is drained by
+//! `AudioClass::write_fifo`.
+//!
+//! ```ignore
+//! static FIFO: AudioFifo<i16, 2, 256> = AudioFifo::new(0);
+//!
+//! let (mut producer, mut consumer) = FIFO.split().unwrap();
+//! // USB task
+//! usb_audio.read_fifo(&mut producer).ok();
+//! // DMA half-buffer interrupt
+//! consumer.pop(&mut dma_half_buffer);
+//! ```
+//!
+//! The FIFO does not block. If the consumer requests more frames than
+//! available (underrun), the missing frames are filled with the silence
+//! value. If the producer adds more frames than free (overrun), the oldest
+//! frames are dropped. Frames the consumer is copying at the time of the
+//! overrun are not overwritten; the producer drops the incoming frames
+//! instead.
+//!
+//! The FIFO requires atomic compare-and-swap operations and is not available
+//! on targets lacking them, e.g. ARMv6-M.
+//!
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// FIFO of up to `N` audio frames of `C` channels of samples of type `S`. `N`
+/// must be a power of two.
+pub struct AudioFifo<S, const C: usize, const N: usize> {
+    buffer: UnsafeCell<[[S; C]; N]>,
+    silence: S,
+    /// Number of frames written so far (wrapping)
+    write: AtomicUsize,
+    /// Number of frames claimed by the consumer or dropped by the producer so
+    /// far (wrapping)
+    read: AtomicUsize,
+    /// First frame claimed by the consumer while `reading` is set
+    claim: AtomicUsize,
+    /// Frame following the frames claimed by the consumer
+    claim_end: AtomicUsize,
+    /// The consumer is copying the claimed frames
+    reading: AtomicBool,
+    underruns: AtomicUsize,
+    overruns: AtomicUsize,
+    taken: AtomicBool,
+}
+
+// The buffer is accessed by one producer and one consumer only. The consumer
+// claims frames by advancing the read counter before copying them and the
+// producer does not overwrite the slots of claimed frames being copied.
+unsafe impl<S: Send, const C: usize, const N: usize> Sync for AudioFifo<S, C, N> {}
+
+impl<S: Copy, const C: usize, const N: usize> AudioFifo<S, C, N> {
+    /// Create an empty FIFO. `silence` is the sample value output on
+    /// underrun, e.g. 0 for signed PCM samples. Panics if `N` is not a power
+    /// of two, which is a compile time error for a FIFO in a `static`.
+    pub const fn new(silence: S) -> AudioFifo<S, C, N> {
+        assert!(N.is_power_of_two(), "size must be a power of two");
+        AudioFifo {
+            buffer: UnsafeCell::new([[silence; C]; N]),
+            silence,
+            write: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            claim: AtomicUsize::new(0),
+            claim_end: AtomicUsize::new(0),
+            reading: AtomicBool::new(false),
+            underruns: AtomicUsize::new(0),
+            overruns: AtomicUsize::new(0),
+            taken: AtomicBool::new(false),
+        }
+    }
+
+    /// Split the FIFO into its producer and its consumer. Returns `None` if
+    /// the FIFO has been split before.
+    pub fn split(&self) -> Option<(Producer<'_, S, C, N>, Consumer<'_, S, C, N>)> {
+        if self.taken.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some((Producer { fifo: self }, Consumer { fifo: self }))
+    }
+
+    /// Maximum number of frames
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Number of frames currently stored (fill level)
+    pub fn len(&self) -> usize {
+        let read = self.read.load(Ordering::Acquire);
+        let write = self.write.load(Ordering::Acquire);
+        write.wrapping_sub(read).min(N)
+    }
+
+    /// Check if the FIFO is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of `Consumer::pop` calls that found less frames than requested
+    pub fn underruns(&self) -> usize {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Number of `Producer::push` calls that dropped frames
+    pub fn overruns(&self) -> usize {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    /// Pointer to the slot of frame number `count`
+    fn slot(&self, count: usize) -> *mut [S; C] {
+        // N is a power of two such that the slots stay consistent when the
+        // counters wrap
+        unsafe { (self.buffer.get() as *mut [S; C]).add(count % N) }
+    }
+}
+
+/// Producer side of an `AudioFifo`
+pub struct Producer<'a, S, const C: usize, const N: usize> {
+    fifo: &'a AudioFifo<S, C, N>,
+}
+
+impl<S: Copy, const C: usize, const N: usize> Producer<'_, S, C, N> {
+    /// Append frames. If the frames do not fit, the oldest frames are dropped
+    /// and the overrun is counted. Returns the number of frames appended,
+    /// which is less than the number of frames if the consumer is copying the
+    /// oldest frames at the time of the overrun.
+    pub fn push(&mut self, frames: &[[S; C]]) -> usize {
+        self.push_iter(frames.iter().copied())
+    }
+
+    /// Append the frames yielded by an iterator. See `push`.
+    pub fn push_iter<I: IntoIterator<Item = [S; C]>>(&mut self, frames: I) -> usize {
+        let fifo = self.fifo;
+        let mut write = fifo.write.load(Ordering::Relaxed);
+        let mut appended = 0;
+        let mut dropped = false;
+        for frame in frames {
+            // drop the oldest frame to make room
+            loop {
+                let read = fifo.read.load(Ordering::SeqCst);
+                if write.wrapping_sub(read) < N {
+                    break;
+                }
+                dropped |= fifo
+                    .read
+                    .compare_exchange(
+                        read,
+                        read.wrapping_add(1),
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    )
+                    .is_ok();
+            }
+            // the slot may hold a frame the consumer is still copying
+            if fifo.reading.load(Ordering::SeqCst) {
+                let claim = fifo.claim.load(Ordering::SeqCst);
+                let claim_end = fifo.claim_end.load(Ordering::SeqCst);
+                let replaced = write.wrapping_sub(N);
+                if replaced.wrapping_sub(claim) < claim_end.wrapping_sub(claim) {
+                    dropped = true;
+                    continue;
+                }
+            }
+            unsafe { fifo.slot(write).write(frame) };
+            write = write.wrapping_add(1);
+            // publish each frame such that the read counter never passes the
+            // write counter
+            fifo.write.store(write, Ordering::Release);
+            appended += 1;
+        }
+        if dropped {
+            let overruns = fifo.overruns.load(Ordering::Relaxed);
+            fifo.overruns
+                .store(overruns.wrapping_add(1), Ordering::Relaxed);
+        }
+        appended
+    }
+
+    /// Number of frames that can be appended without an overrun
+    pub fn free(&self) -> usize {
+        N - self.fifo.len()
+    }
+
+    /// The FIFO
+    pub fn fifo(&self) -> &AudioFifo<S, C, N> {
+        self.fifo
+    }
+}
+
+/// Consumer side of an `AudioFifo`
+pub struct Consumer<'a, S, const C: usize, const N: usize> {
+    fifo: &'a AudioFifo<S, C, N>,
+}
+
+impl<S: Copy, const C: usize, const N: usize> Consumer<'_, S, C, N> {
+    /// Remove the oldest frames and copy them to `frames`. If less frames
+    /// than requested are available, the remaining frames are filled with
+    /// silence and the underrun is counted. Returns the number of frames
+    /// taken from the FIFO.
+    pub fn pop(&mut self, frames: &mut [[S; C]]) -> usize {
+        let count = frames.len();
+        let mut frames = frames.iter_mut();
+        self.pop_with(count, |frame| {
+            if let Some(slot) = frames.next() {
+                *slot = frame;
+            }
+        })
+    }
+
+    /// Remove the oldest `count` frames and pass them to `f` one by one. See
+    /// `pop`. Frames of silence are passed on underrun. `f` is called while
+    /// the frames are claimed and should return quickly.
+    pub fn pop_with<F: FnMut([S; C])>(&mut self, count: usize, mut f: F) -> usize {
+        let fifo = self.fifo;
+        let (read, taken) = loop {
+            let read = fifo.read.load(Ordering::SeqCst);
+            let write = fifo.write.load(Ordering::Acquire);
+            let taken = write.wrapping_sub(read).min(count);
+            fifo.claim.store(read, Ordering::SeqCst);
+            fifo.claim_end
+                .store(read.wrapping_add(taken), Ordering::SeqCst);
+            fifo.reading.store(true, Ordering::SeqCst);
+            // fails if the producer has dropped frames in the meantime
+            if fifo
+                .read
+                .compare_exchange(
+                    read,
+                    read.wrapping_add(taken),
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .is_ok()
+            {
+                break (read, taken);
+            }
+        };
+        for ndx in 0..taken {
+            f(unsafe { fifo.slot(read.wrapping_add(ndx)).read() });
+        }
+        fifo.reading.store(false, Ordering::SeqCst);
+        if taken < count {
+            for _ in taken..count {
+                f([fifo.silence; C]);
+            }
+            let underruns = fifo.underruns.load(Ordering::Relaxed);
+            fifo.underruns
+                .store(underruns.wrapping_add(1), Ordering::Relaxed);
+        }
+        taken
+    }
+
+    /// The FIFO
+    pub fn fifo(&self) -> &AudioFifo<S, C, N> {
+        self.fifo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_once() {
+        let fifo = AudioFifo::<i16, 1, 4>::new(0);
+        assert!(fifo.split().is_some());
+        assert!(fifo.split().is_none());
+    }
+
+    #[test]
+    fn wrap() {
+        let fifo = AudioFifo::<i16, 2, 4>::new(0);
+        let (mut producer, mut consumer) = fifo.split().unwrap();
+        let mut frames = [[0; 2]; 3];
+        for n in 0..10 {
+            assert_eq!(
+                producer.push(&[[n, -n], [n + 1, -n - 1], [n + 2, -n - 2]]),
+                3
+            );
+            assert_eq!(fifo.len(), 3);
+            assert_eq!(consumer.pop(&mut frames), 3);
+            assert_eq!(frames, [[n, -n], [n + 1, -n - 1], [n + 2, -n - 2]]);
+        }
+        assert!(fifo.is_empty());
+        assert_eq!((fifo.underruns(), fifo.overruns()), (0, 0));
+    }
+
+    #[test]
+    fn overrun_drops_oldest() {
+        let fifo = AudioFifo::<i16, 1, 4>::new(0);
+        let (mut producer, mut consumer) = fifo.split().unwrap();
+        assert_eq!(producer.push(&[[1], [2], [3]]), 3);
+        assert_eq!(producer.push(&[[4], [5], [6], [7], [8], [9]]), 6);
+        assert_eq!(fifo.len(), 4);
+        assert_eq!(producer.free(), 0);
+        assert_eq!(fifo.overruns(), 1);
+        let mut frames = [[0]; 4];
+        assert_eq!(consumer.pop(&mut frames), 4);
+        assert_eq!(frames, [[6], [7], [8], [9]]);
+        assert_eq!(fifo.underruns(), 0);
+    }
+
+    #[test]
+    fn underrun_fills_silence() {
+        let fifo = AudioFifo::<u8, 2, 8>::new(0x80);
+        let (mut producer, mut consumer) = fifo.split().unwrap();
+        producer.push(&[[1, 2], [3, 4]]);
+        let mut frames = [[0; 2]; 4];
+        assert_eq!(consumer.pop(&mut frames), 2);
+        assert_eq!(frames, [[1, 2], [3, 4], [0x80, 0x80], [0x80, 0x80]]);
+        assert_eq!(fifo.underruns(), 1);
+        assert_eq!(consumer.pop(&mut frames), 0);
+        assert_eq!(frames, [[0x80, 0x80]; 4]);
+        assert_eq!(fifo.underruns(), 2);
+        assert_eq!(fifo.overruns(), 0);
+    }
+
+    #[test]
+    fn pop_with_passes_each_frame() {
+        let fifo = AudioFifo::<i16, 1, 4>::new(0);
+        let (mut producer, mut consumer) = fifo.split().unwrap();
+        producer.push(&[[1], [2], [3]]);
+        let mut sum = 0;
+        assert_eq!(consumer.pop_with(2, |[sample]| sum += sample), 2);
+        assert_eq!((sum, fifo.len(), fifo.underruns()), (3, 1, 0));
+        let mut calls = 0;
+        assert_eq!(consumer.pop_with(3, |_| calls += 1), 1);
+        assert_eq!((calls, fifo.underruns()), (3, 1));
+    }
+}
@@ -36,7 +36,8 @@
 
 use class_codes::*;
 use core::convert::From;
-use usb_device::control::{Recipient, Request, RequestType};
+use usb_device::control::{self, Recipient, Request, RequestType};
+use usb_device::descriptor::descriptor_type;
 use usb_device::device::DEFAULT_ALTERNATE_SETTING;
 use usb_device::endpoint::{Endpoint, EndpointDirection, In, Out};
 use usb_device::{class_prelude::*, UsbDirection};
@@ -44,18 +45,207 @@ use usb_device::{class_prelude::*, UsbDirection};
 mod terminal_type;
 pub use terminal_type::TerminalType;
 mod class_codes;
+mod cluster;
+use cluster::Cluster;
+pub use cluster::SpatialLocation;
+pub mod codec;
+mod feature_unit;
+#[cfg(target_has_atomic = "ptr")]
+pub mod fifo;
+pub mod resampler;
+pub use feature_unit::FeatureUnitConfig;
+mod mixer_unit;
+mod selector_unit;
+pub use mixer_unit::{MixerConfig, MixerSource};
+mod processing_unit;
+pub use processing_unit::{ProcessingControl, ProcessingUnitConfig};
+mod extension_unit;
+pub use extension_unit::ExtensionUnitConfig;
+mod sample;
+pub use sample::Sample;
+mod feedback;
+pub use feedback::{FeedbackEstimator, FeedbackFormat};
+mod packet_scheduler;
+pub use packet_scheduler::PacketScheduler;
+mod control_handler;
+pub use control_handler::{Attribute, AudioControlHandler, ControlRequest, ControlTarget};
+mod topology;
+use topology::{Entities, StreamUnits};
+pub use topology::{Entity, Topology, MAX_ENTITIES};
 
-const ID_INPUT_TERMINAL: u8 = 0x01;
-const ID_OUTPUT_TERMINAL: u8 = 0x02;
+/// Maximum number of streams per direction
+pub const MAX_STREAMS: usize = 4;
 
 const MAX_ISO_EP_SIZE: u32 = 1023;
 
+/// Size of a full-speed feedback value in 10.14 format
+const FEEDBACK_SIZE: u16 = 3;
+
+/// Feedback endpoint refresh (2^5 ms) used if not configured otherwise
+const DEFAULT_FEEDBACK_REFRESH: u8 = 5;
+
+/// Size of the status word sent by the status interrupt endpoint
+const STATUS_SIZE: u16 = 2;
+
+/// bStatusType bit indicating that the originator has an interrupt pending
+const STATUS_INTERRUPT_PENDING: u8 = 0x80;
+
+/// Maximum size of the parameter block of a request passed to the
+/// `AudioControlHandler`
+pub const MAX_CONTROL_SIZE: usize = 64;
+
 #[derive(Clone, Copy, Debug)]
 pub enum Format {
     /// Signed, 16 bits per subframe, little endian
     S16le,
     /// Signed, 24 bits per subframe, little endian
     S24le,
+    /// Signed, 32 bits per subframe, little endian
+    S32le,
+    /// IEEE 754 single precision floating point, 32 bits per subframe, little
+    /// endian
+    F32le,
+    /// Signed PCM, little endian, with a subframe size of `subframe_bytes`
+    /// (1 to 4) bytes of which `bits` bits are used, e.g. 24 bit samples
+    /// carried in 4 byte subframes. The valid bits are left-justified in the
+    /// subframe. `bits` must not exceed 8 * `subframe_bytes`.
+    Pcm { subframe_bytes: u8, bits: u8 },
+    /// Unsigned, 8 bits per subframe. See `codec::u8_encode`.
+    U8,
+    /// ITU-T G.711 A-law, 8 bits per subframe. See `codec::alaw_encode`.
+    ALaw,
+    /// ITU-T G.711 µ-law, 8 bits per subframe. See `codec::mulaw_encode`.
+    MuLaw,
+    /// MPEG compressed audio (Type II format). `capabilities` and `features`
+    /// are the bmMPEGCapabilities and bmMPEGFeatures bitmaps of the MPEG
+    /// Format-Specific Descriptor. Requires `StreamConfig::new_compressed`.
+    Mpeg { capabilities: u16, features: u8 },
+    /// AC-3 compressed audio (Type II format). `bsid` and `features` are the
+    /// bmBSID and bmAC3Features bitmaps of the AC-3 Format-Specific
+    /// Descriptor. Requires `StreamConfig::new_compressed`.
+    Ac3 { bsid: u32, features: u8 },
+    /// IEC 61937 bursts of non-PCM audio data (Type III format) carried in
+    /// two 16 bit subframes, e.g. for an S/PDIF passthrough. A stream of this
+    /// format must have two channels.
+    Iec61937(Iec61937),
+}
+
+/// Type of the non-PCM audio data carried in IEC 61937 bursts
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Iec61937 {
+    /// AC-3
+    Ac3,
+    /// MPEG-1 Layer 1
+    Mpeg1Layer1,
+    /// MPEG-1 Layer 2 or 3 or MPEG-2 without extension
+    Mpeg1Layer23,
+    /// MPEG-2 with extension
+    Mpeg2Ext,
+    /// MPEG-2 Layer 1 low sampling frequency
+    Mpeg2Layer1Ls,
+    /// MPEG-2 Layer 2 or 3 low sampling frequency
+    Mpeg2Layer23Ls,
+}
+
+impl Format {
+    /// Size of a subframe (one sample of one channel) in bytes or 0 for
+    /// compressed formats
+    fn subframe_size(self) -> u8 {
+        match self {
+            Format::S16le | Format::Iec61937(_) => 2,
+            Format::S24le => 3,
+            Format::S32le | Format::F32le => 4,
+            Format::Pcm { subframe_bytes, .. } => subframe_bytes,
+            Format::U8 | Format::ALaw | Format::MuLaw => 1,
+            Format::Mpeg { .. } | Format::Ac3 { .. } => 0,
+        }
+    }
+
+    /// Number of bits used by a sample
+    fn bit_resolution(self) -> u8 {
+        match self {
+            Format::Pcm { bits, .. } => bits,
+            _ => self.subframe_size() * 8,
+        }
+    }
+
+    /// Check if the subframe size and the bit resolution of a Type I or Type
+    /// III format as well as the number of channels are valid
+    fn check(self, channels: u8) -> Result<()> {
+        let subframe_size = self.subframe_size();
+        let bits = self.bit_resolution();
+        if (1..=4).contains(&subframe_size)
+            && bits > 0
+            && bits <= 8 * subframe_size
+            && (self.format_type() != FORMAT_TYPE_III || channels == 2)
+        {
+            Ok(())
+        } else {
+            Err(Error::InvalidValue)
+        }
+    }
+
+    /// wFormatTag of the Class-specific AS General Interface Descriptor
+    fn format_tag(self) -> u16 {
+        match self {
+            Format::S16le | Format::S24le | Format::S32le | Format::Pcm { .. } => PCM,
+            Format::F32le => IEEE_FLOAT,
+            Format::U8 => PCM8,
+            Format::ALaw => ALAW,
+            Format::MuLaw => MULAW,
+            Format::Mpeg { .. } => MPEG,
+            Format::Ac3 { .. } => AC_3,
+            Format::Iec61937(data_type) => match data_type {
+                Iec61937::Ac3 => IEC1937_AC_3,
+                Iec61937::Mpeg1Layer1 => IEC1937_MPEG_1_LAYER1,
+                Iec61937::Mpeg1Layer23 => IEC1937_MPEG_1_LAYER2_3,
+                Iec61937::Mpeg2Ext => IEC1937_MPEG_2_EXT,
+                Iec61937::Mpeg2Layer1Ls => IEC1937_MPEG_2_LAYER1_LS,
+                Iec61937::Mpeg2Layer23Ls => IEC1937_MPEG_2_LAYER2_3_LS,
+            },
+        }
+    }
+
+    /// bFormatType of the Format Type Descriptor
+    fn format_type(self) -> u8 {
+        match self {
+            Format::Mpeg { .. } | Format::Ac3 { .. } => FORMAT_TYPE_II,
+            Format::Iec61937(_) => FORMAT_TYPE_III,
+            _ => FORMAT_TYPE_I,
+        }
+    }
+}
+
+/// Synchronization type of an isochronous audio data endpoint
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncType {
+    /// The device runs on its own clock. For output streams, an explicit
+    /// feedback endpoint is used.
+    Asynchronous,
+    /// The device adapts to the data rate of the host (output streams) or
+    /// produces data at a rate requested by the host (input streams).
+    Adaptive,
+    /// The device clock is locked to the USB Start Of Frame
+    Synchronous,
+}
+
+impl From<SyncType> for IsochronousSynchronizationType {
+    fn from(sync_type: SyncType) -> IsochronousSynchronizationType {
+        match sync_type {
+            SyncType::Asynchronous => IsochronousSynchronizationType::Asynchronous,
+            SyncType::Adaptive => IsochronousSynchronizationType::Adaptive,
+            SyncType::Synchronous => IsochronousSynchronizationType::Synchronous,
+        }
+    }
+}
+
+/// Time needed by an endpoint to lock its internal clock recovery circuitry
+#[derive(Clone, Copy, Debug)]
+pub enum LockDelay {
+    /// Lock delay in milliseconds
+    Milliseconds(u16),
+    /// Lock delay in decoded PCM samples
+    DecodedSamples(u16),
 }
 
 /// Sampling rates that shall be supported by an steaming endpoint
@@ -69,18 +259,115 @@ pub enum Rates<'a> {
     Discrete(&'a [u32]),
 }
 
+impl Rates<'_> {
+    /// Check if a sampling rate is supported
+    fn contains(&self, rate: u32) -> bool {
+        match *self {
+            Rates::Continuous(min, max) => (min..=max).contains(&rate),
+            Rates::Discrete(rates) => rates.contains(&rate),
+        }
+    }
+
+    /// Highest supported sampling rate
+    fn max(&self) -> u32 {
+        match *self {
+            Rates::Continuous(_, max) => max,
+            Rates::Discrete(rates) => rates.iter().copied().max().unwrap_or(0),
+        }
+    }
+}
+
+/// Names of the Units inserted into a stream by its `StreamConfig`. The names
+/// are provided as string descriptors (iSelector, iExtension, iFeature,
+/// iMixer and iProcessing) and typically shown by the host for the controls
+/// of the Units. Names of Units that are not configured are ignored.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UnitNames<'a> {
+    selector_unit: Option<&'a str>,
+    extension_unit: Option<&'a str>,
+    feature_unit: Option<&'a str>,
+    mixer_unit: Option<&'a str>,
+    processing_units: &'a [&'a str],
+}
+
+impl<'a> UnitNames<'a> {
+    /// Create a set of names naming no Unit
+    pub fn new() -> UnitNames<'a> {
+        UnitNames::default()
+    }
+
+    /// Name the Selector Unit, see `StreamConfig::with_selector`
+    pub fn selector_unit(self, name: &'a str) -> UnitNames<'a> {
+        UnitNames {
+            selector_unit: Some(name),
+            ..self
+        }
+    }
+
+    /// Name the Extension Unit, see `StreamConfig::with_extension_unit`
+    pub fn extension_unit(self, name: &'a str) -> UnitNames<'a> {
+        UnitNames {
+            extension_unit: Some(name),
+            ..self
+        }
+    }
+
+    /// Name the Feature Unit, see `StreamConfig::with_feature_unit`
+    pub fn feature_unit(self, name: &'a str) -> UnitNames<'a> {
+        UnitNames {
+            feature_unit: Some(name),
+            ..self
+        }
+    }
+
+    /// Name the Mixer Unit, see `StreamConfig::with_mixer`
+    pub fn mixer_unit(self, name: &'a str) -> UnitNames<'a> {
+        UnitNames {
+            mixer_unit: Some(name),
+            ..self
+        }
+    }
+
+    /// Name the Processing Units in the order of their configurations, see
+    /// `StreamConfig::with_processing_units`
+    pub fn processing_units(self, names: &'a [&'a str]) -> UnitNames<'a> {
+        UnitNames {
+            processing_units: names,
+            ..self
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct StreamConfig<'a> {
     format: Format,
     channels: u8,
     rates: Rates<'a>,
     terminal_type: TerminalType,
+    terminal_name: Option<&'a str>,
+    unit_names: Option<&'a UnitNames<'a>>,
+    /// Spatial locations of the predefined channels (wChannelConfig)
+    channel_config: Option<u16>,
+    channel_names: &'a [&'a str],
+    feature_unit: Option<FeatureUnitConfig>,
+    selector_inputs: &'a [TerminalType],
+    extension_unit: Option<ExtensionUnitConfig<'a>>,
+    mixer: Option<MixerConfig<'a>>,
+    processing_units: &'a [ProcessingUnitConfig<'a>],
+    feedback_refresh: Option<u8>,
+    sync_type: Option<SyncType>,
+    lock_delay: Option<LockDelay>,
+    alternates: &'a [StreamConfig<'a>],
+    /// wMaxBitRate in kbits/s (Type II formats only)
+    max_bit_rate: u16,
+    /// wSamplesPerFrame (Type II formats only)
+    samples_per_frame: u16,
     /// ISO endpoint size calculated from format, channels and rates (may be
     /// removed in future)
     ep_size: u16,
 }
 
-impl StreamConfig<'_> {
+impl<'a> StreamConfig<'a> {
     /// Create a stream configuration with one or more discrete sampling rates
     /// indicated in samples/second. An input stream or an output stream will
     /// have an Input Terminal or Output Terminal of Terminal Type
@@ -91,6 +378,7 @@ impl StreamConfig<'_> {
         rates: &'_ [u32],
         terminal_type: TerminalType,
     ) -> Result<StreamConfig<'_>> {
+        format.check(channels)?;
         let max_rate = rates.iter().max().unwrap();
         let ep_size = Self::ep_size(format, channels, *max_rate)?;
         let rates = Rates::Discrete(rates);
@@ -99,6 +387,21 @@ impl StreamConfig<'_> {
             channels,
             rates,
             terminal_type,
+            terminal_name: None,
+            unit_names: None,
+            channel_config: None,
+            channel_names: &[],
+            feature_unit: None,
+            selector_inputs: &[],
+            extension_unit: None,
+            mixer: None,
+            processing_units: &[],
+            feedback_refresh: None,
+            sync_type: None,
+            lock_delay: None,
+            alternates: &[],
+            max_bit_rate: 0,
+            samples_per_frame: 0,
             ep_size,
         })
     }
@@ -117,6 +420,7 @@ impl StreamConfig<'_> {
         if min_rate >= max_rate {
             return Err(Error::InvalidValue);
         }
+        format.check(channels)?;
         let ep_size = Self::ep_size(format, channels, max_rate)?;
         let rates = Rates::Continuous(min_rate, max_rate);
         Ok(StreamConfig {
@@ -124,18 +428,334 @@ impl StreamConfig<'_> {
             channels,
             rates,
             terminal_type,
+            terminal_name: None,
+            unit_names: None,
+            channel_config: None,
+            channel_names: &[],
+            feature_unit: None,
+            selector_inputs: &[],
+            extension_unit: None,
+            mixer: None,
+            processing_units: &[],
+            feedback_refresh: None,
+            sync_type: None,
+            lock_delay: None,
+            alternates: &[],
+            max_bit_rate: 0,
+            samples_per_frame: 0,
             ep_size,
         })
     }
 
-    /// calculate ISO endpoint size from format, channels and rates
+    /// Create a stream configuration for compressed audio data of a Type II
+    /// format (`Format::Mpeg` or `Format::Ac3`). `max_bit_rate` is the maximum
+    /// bit rate in kbits/s, which determines the ISO endpoint size, and
+    /// `samples_per_frame` is the number of PCM samples per channel decoded
+    /// from one encoded audio frame. `channels` is the number of logical
+    /// channels of the decoded audio data and `rates` are the supported
+    /// sampling rates of the decoded audio data in samples/second.
+    pub fn new_compressed(
+        format: Format,
+        channels: u8,
+        max_bit_rate: u16,
+        samples_per_frame: u16,
+        rates: &'_ [u32],
+        terminal_type: TerminalType,
+    ) -> Result<StreamConfig<'_>> {
+        if format.format_type() != FORMAT_TYPE_II || rates.is_empty() {
+            return Err(Error::InvalidValue);
+        }
+        // a frame of 1 ms carries up to max_bit_rate / 8 bytes
+        let ep_size = (max_bit_rate as u32).div_ceil(8);
+        if ep_size > MAX_ISO_EP_SIZE {
+            return Err(Error::BandwidthExceeded);
+        }
+        let rates = Rates::Discrete(rates);
+        Ok(StreamConfig {
+            format,
+            channels,
+            rates,
+            terminal_type,
+            terminal_name: None,
+            unit_names: None,
+            channel_config: None,
+            channel_names: &[],
+            feature_unit: None,
+            selector_inputs: &[],
+            extension_unit: None,
+            mixer: None,
+            processing_units: &[],
+            feedback_refresh: None,
+            sync_type: None,
+            lock_delay: None,
+            alternates: &[],
+            max_bit_rate,
+            samples_per_frame,
+            ep_size: ep_size as u16,
+        })
+    }
+
+    /// Offer further alternate settings having different formats or sampling
+    /// rates. The stream configuration itself describes Alternate Setting 1
+    /// whereas `alternates` describe the Alternate Settings 2, 3, .... Only
+    /// format and sampling rates of the alternates are used; all other
+    /// properties of the stream, such as the terminal type, are taken from
+    /// this stream configuration. Returns an error if the number of channels
+    /// of an alternate differs from the number of channels of the stream
+    /// because the Terminals and Units of the stream are shared by all
+    /// Alternate Settings.
+    pub fn with_alternates(self, alternates: &'a [StreamConfig<'a>]) -> Result<Self> {
+        if alternates.iter().any(|alt| alt.channels != self.channels) {
+            return Err(Error::InvalidValue);
+        }
+        Ok(StreamConfig { alternates, ..self })
+    }
+
+    /// Name the Input Terminal of an input stream or the Output Terminal of
+    /// an output stream, i.e. the Terminal of Terminal Type `terminal_type`.
+    /// The name is provided as string descriptor (iTerminal).
+    pub fn with_terminal_name(self, name: &'a str) -> Self {
+        StreamConfig {
+            terminal_name: Some(name),
+            ..self
+        }
+    }
+
+    /// Name the Units inserted into the stream, e.g. the Feature Unit
+    /// configured by `with_feature_unit`. See `UnitNames`.
+    pub fn with_unit_names(self, names: &'a UnitNames<'a>) -> Self {
+        StreamConfig {
+            unit_names: Some(names),
+            ..self
+        }
+    }
+
+    /// Declare the spatial locations of the channels of the stream. The
+    /// predefined channels having the spatial locations `locations` are
+    /// followed by non-predefined channels named `names`, which are provided
+    /// as string descriptors (iChannelNames). The locations must be given in
+    /// the order of `SpatialLocation`, which is the order of the channels in
+    /// the stream. Returns an error if a location is repeated or out of order
+    /// or if the total number of channels does not match the number of
+    /// channels of the stream. Without this declaration, the spatial
+    /// locations are derived from the number of channels.
+    pub fn with_channel_config(
+        self,
+        locations: &[SpatialLocation],
+        names: &'a [&'a str],
+    ) -> Result<Self> {
+        if locations.windows(2).any(|pair| pair[0] >= pair[1])
+            || (self.channels != 0 && locations.len() + names.len() != self.channels as usize)
+        {
+            return Err(Error::InvalidValue);
+        }
+        let channel_config = locations.iter().fold(0, |config, l| config | l.bit());
+        Ok(StreamConfig {
+            channel_config: Some(channel_config),
+            channel_names: names,
+            ..self
+        })
+    }
+
+    /// Logical channel cluster of the stream. Consecutive string indices are
+    /// allocated for the names of the non-predefined channels.
+    fn cluster<B: UsbBus>(&self, alloc: &UsbBusAllocator<B>) -> Cluster {
+        let Some(config) = self.channel_config else {
+            return Cluster::new(self.channels);
+        };
+        let mut names = 0;
+        for ndx in 0..self.channel_names.len() {
+            let index = alloc.string().into();
+            if ndx == 0 {
+                names = index;
+            }
+        }
+        Cluster {
+            channels: self.channels,
+            config,
+            names,
+        }
+    }
+
+    /// Audio data format
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Number of channels
+    pub fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    /// Supported sampling rates
+    pub fn rates(&self) -> &Rates<'a> {
+        &self.rates
+    }
+
+    /// Stream configurations of the Alternate Settings 1, 2, ...
+    fn alt_configs(&self) -> impl Iterator<Item = &StreamConfig<'a>> {
+        core::iter::once(self).chain(self.alternates.iter())
+    }
+
+    /// Stream configuration of an Alternate Setting
+    fn alt_config(&self, alt_setting: u8) -> Option<&StreamConfig<'a>> {
+        let ndx = alt_setting.checked_sub(1)?;
+        self.alt_configs().nth(ndx as usize)
+    }
+
+    /// Subframe size of the samples of type `S` of audio frames having
+    /// `channels` channels. Returns an error if the format or the number of
+    /// channels of the stream does not match.
+    fn sample_subframe_size<S: Sample>(&self, channels: usize) -> Result<usize> {
+        if S::supports(self.format) && channels == self.channels as usize {
+            Ok(self.format.subframe_size() as usize)
+        } else {
+            Err(Error::InvalidValue)
+        }
+    }
+
+    /// ISO endpoint size needed for all Alternate Settings
+    fn max_ep_size(&self) -> u16 {
+        self.alt_configs().map(|c| c.ep_size).max().unwrap_or(0)
+    }
+
+    /// Insert a Feature Unit between the Input Terminal and the Output
+    /// Terminal of the stream. A Feature Unit is supported for streams having
+    /// up to 8 channels.
+    pub fn with_feature_unit(self, config: FeatureUnitConfig) -> Self {
+        StreamConfig {
+            feature_unit: Some(config),
+            ..self
+        }
+    }
+
+    /// Insert a Selector Unit in front of the Feature Unit or the Output
+    /// Terminal of the stream. The Selector Unit selects between the Input
+    /// Terminal of the stream (Input Pin 1) and further Input Terminals of the
+    /// Terminal Types `terminal_types` (Input Pins 2, 3, ...), e.g. to choose
+    /// between several physical inputs of a USB capture stream. All Input
+    /// Terminals have the number of channels of the stream. Up to 7 further
+    /// Input Terminals are supported.
+    pub fn with_selector(self, terminal_types: &'a [TerminalType]) -> Self {
+        StreamConfig {
+            selector_inputs: terminal_types,
+            ..self
+        }
+    }
+
+    /// Insert an Extension Unit in front of the Feature Unit or the Output
+    /// Terminal of the stream. The Extension Unit follows the Selector Unit,
+    /// if any. Requests to its controls are forwarded to the
+    /// `AudioControlHandler` of the `AudioClass`. See `ExtensionUnitConfig`.
+    pub fn with_extension_unit(self, config: ExtensionUnitConfig<'a>) -> Self {
+        StreamConfig {
+            extension_unit: Some(config),
+            ..self
+        }
+    }
+
+    /// Insert a Mixer Unit in front of the Output Terminal of the stream that
+    /// mixes the signals of other streams into the stream, e.g. a microphone
+    /// signal into the headphone playback for monitoring. See `MixerConfig`.
+    pub fn with_mixer(self, config: MixerConfig<'a>) -> Self {
+        StreamConfig {
+            mixer: Some(config),
+            ..self
+        }
+    }
+
+    /// Insert a chain of up to 4 Processing Units in front of the Output
+    /// Terminal of the stream. The Processing Units follow the Mixer Unit, if
+    /// any. An Up/Down-mix or Dolby Prologic Processing Unit may change the
+    /// number of channels of an output stream; the last Processing Unit of an
+    /// input stream must have the number of channels of the stream. See
+    /// `ProcessingUnitConfig`.
+    pub fn with_processing_units(self, configs: &'a [ProcessingUnitConfig<'a>]) -> Self {
+        StreamConfig {
+            processing_units: configs,
+            ..self
+        }
+    }
+
+    /// Use asynchronous synchronization for an output stream. An isochronous
+    /// feedback endpoint is allocated, which is polled by the host every
+    /// 2^`refresh` milliseconds. `refresh` must be in the range 1 to 9. The
+    /// feedback value is provided by means of
+    /// `AudioClass::set_output_feedback`. Building an `AudioClass` with an
+    /// input stream or a non-asynchronous stream using this option fails.
+    pub fn with_feedback(self, refresh: u8) -> Self {
+        StreamConfig {
+            feedback_refresh: Some(refresh),
+            ..self
+        }
+    }
+
+    /// Set the synchronization type of the isochronous endpoint. Defaults to
+    /// `SyncType::Asynchronous` for input streams and to `SyncType::Adaptive`
+    /// for output streams unless a feedback endpoint is configured by means of
+    /// `with_feedback`. An asynchronous output stream always has a feedback
+    /// endpoint.
+    pub fn with_sync_type(self, sync_type: SyncType) -> Self {
+        StreamConfig {
+            sync_type: Some(sync_type),
+            ..self
+        }
+    }
+
+    /// Set the lock delay indicated in the class-specific endpoint descriptor
+    pub fn with_lock_delay(self, lock_delay: LockDelay) -> Self {
+        StreamConfig {
+            lock_delay: Some(lock_delay),
+            ..self
+        }
+    }
+
+    /// Streams connected to the Mixer Unit
+    fn mixer_sources(&self) -> &'a [MixerSource] {
+        self.mixer.map_or(&[], |config| config.sources())
+    }
+
+    /// Streams connected to the further Input Pins of the Extension Unit
+    fn extension_unit_sources(&self) -> &'a [MixerSource] {
+        self.extension_unit
+            .map_or(&[], |config| config.source_streams())
+    }
+
+    /// Check if Units are configured for the stream
+    fn has_units(&self) -> bool {
+        !self.selector_inputs.is_empty()
+            || self.extension_unit.is_some()
+            || self.feature_unit.is_some()
+            || self.mixer.is_some()
+            || !self.processing_units.is_empty()
+    }
+
+    /// Effective synchronization type of a stream in direction `direction`
+    fn sync_type(&self, direction: UsbDirection) -> SyncType {
+        self.sync_type.unwrap_or(match direction {
+            UsbDirection::In => SyncType::Asynchronous,
+            UsbDirection::Out if self.feedback_refresh.is_some() => SyncType::Asynchronous,
+            UsbDirection::Out => SyncType::Adaptive,
+        })
+    }
+
+    /// Refresh of the feedback endpoint of a stream in direction `direction`
+    /// or `None` if the stream has no feedback endpoint
+    fn feedback_refresh(&self, direction: UsbDirection) -> Option<u8> {
+        if direction == UsbDirection::Out && self.sync_type(direction) == SyncType::Asynchronous {
+            Some(self.feedback_refresh.unwrap_or(DEFAULT_FEEDBACK_REFRESH))
+        } else {
+            self.feedback_refresh
+        }
+    }
+
+    /// calculate ISO endpoint size from format, channels and rates. A packet
+    /// carries up to the number of audio frames per USB frame rounded up
+    /// (e.g. 45 at 44.1 kHz) plus one extra frame, which the host or an
+    /// asynchronous or adaptive device may add to adjust the data rate.
     fn ep_size(format: Format, channels: u8, max_rate: u32) -> Result<u16> {
-        let octets_per_frame = channels as u32
-            * match format {
-                Format::S16le => 2,
-                Format::S24le => 3,
-            };
-        let ep_size = octets_per_frame * max_rate / 1000;
+        let octets_per_frame = channels as u32 * format.subframe_size() as u32;
+        let ep_size = octets_per_frame * (max_rate.div_ceil(1000) + 1);
         if ep_size > MAX_ISO_EP_SIZE {
             return Err(Error::BandwidthExceeded);
         }
@@ -161,12 +781,137 @@ impl From<UsbError> for Error {
 /// Result type alias for the USB Audio Class
 type Result<T> = core::result::Result<T, Error>;
 
+/// Explicit feedback endpoint of an asynchronous output stream
+struct FeedbackEndpoint<'a, B: UsbBus> {
+    endpoint: Endpoint<'a, B, In>,
+    refresh: u8,
+    /// Feedback value in 10.14 format
+    value: u32,
+}
+
+impl<B: UsbBus> FeedbackEndpoint<'_, B> {
+    /// Nominal feedback value for a sampling rate
+    fn nominal_value(sample_rate: u32) -> u32 {
+        (((sample_rate as u64) << 14) / 1000) as u32
+    }
+
+    /// Load the feedback value into the endpoint buffer to be sent with the
+    /// next poll of the host
+    fn send(&self) {
+        self.endpoint.write(&self.value.to_le_bytes()[..3]).ok();
+    }
+}
+
+/// Status interrupt endpoint of the AudioControl interface
+struct StatusEndpoint<'a, B: UsbBus> {
+    endpoint: Endpoint<'a, B, In>,
+    /// Entities whose status word is still to be sent (bit n: ID n + 1)
+    queued: u32,
+    /// Entities with an interrupt pending, i.e. not yet acknowledged by the
+    /// host (bit n: ID n + 1)
+    pending: u32,
+    /// A status word has been loaded into the endpoint buffer and is not yet
+    /// read by the host
+    busy: bool,
+}
+
+impl<B: UsbBus> StatusEndpoint<'_, B> {
+    /// Queue a status word for the Terminal or Unit `id` and mark its
+    /// interrupt as pending
+    fn notify(&mut self, id: u8) {
+        let bit = 1 << (id - 1);
+        self.queued |= bit;
+        self.pending |= bit;
+        self.send();
+    }
+
+    /// Clear the pending interrupt of the Terminal or Unit `id`. Returns the
+    /// bStatusType field before clearing.
+    fn acknowledge(&mut self, id: u8) -> u8 {
+        let bit = 1 << (id - 1);
+        let status_type = if self.pending & bit != 0 {
+            STATUS_INTERRUPT_PENDING
+        } else {
+            0x00
+        };
+        self.queued &= !bit;
+        self.pending &= !bit;
+        status_type
+    }
+
+    /// Load the status word of the next queued Terminal or Unit into the
+    /// endpoint buffer unless the previous one has not been read yet
+    fn send(&mut self) {
+        if self.busy || self.queued == 0 {
+            return;
+        }
+        let id = self.queued.trailing_zeros() as u8 + 1;
+        // bStatusType: interrupt pending, originator: AudioControl interface
+        let status = [STATUS_INTERRUPT_PENDING, id]; // bStatusType, bOriginator
+        if self.endpoint.write(&status).is_ok() {
+            self.queued &= !(1 << (id - 1));
+            self.busy = true;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.queued = 0;
+        self.pending = 0;
+        self.busy = false;
+    }
+}
+
+/// Pack an audio frame into its subframes
+fn pack_frame<S: Sample>(frame: &[S], data: &mut [u8], subframe_size: usize) {
+    for (sample, subframe) in frame.iter().zip(data.chunks_exact_mut(subframe_size)) {
+        sample.pack(subframe);
+    }
+}
+
+/// Unpack an audio frame from its subframes
+fn unpack_frame<S: Sample, const N: usize>(data: &[u8], subframe_size: usize) -> [S; N] {
+    core::array::from_fn(|ch| S::unpack(&data[ch * subframe_size..(ch + 1) * subframe_size]))
+}
+
+/// Write a Standard AS Isochronous Endpoint Descriptor (9 bytes). Unlike
+/// `DescriptorWriter::endpoint`, this function allows the maximum packet size
+/// to differ from that of the allocated endpoint.
+fn write_endpoint<B: UsbBus, D: EndpointDirection>(
+    writer: &mut DescriptorWriter,
+    endpoint: &Endpoint<'_, B, D>,
+    max_packet_size: u16,
+    refresh: u8,
+    synch_address: u8,
+) -> usb_device::Result<()> {
+    writer.write(
+        descriptor_type::ENDPOINT,
+        &[
+            endpoint.address().into(),             // bEndpointAddress
+            endpoint.ep_type().to_bm_attributes(), // bmAttributes
+            max_packet_size as u8,
+            (max_packet_size >> 8) as u8, // wMaxPacketSize
+            endpoint.interval(),          // bInterval
+            refresh,                      // bRefresh
+            synch_address,                // bSynchAddress
+        ],
+    )
+}
+
 /// Internal state related to audio streaming in a certain direction
 struct AudioStream<'a, B: UsbBus, D: EndpointDirection> {
     stream_config: StreamConfig<'a>,
     interface: InterfaceNumber,
     endpoint: Endpoint<'a, B, D>,
     alt_setting: u8,
+    /// ID of the USB streaming Terminal linked to the interface
+    terminal_link: u8,
+    /// Units configured by the `StreamConfig`
+    units: StreamUnits,
+    /// Logical channel cluster of the stream
+    cluster: Cluster,
+    sample_rate: u32,
+    sample_rate_changed: bool,
+    feedback: Option<FeedbackEndpoint<'a, B>>,
 }
 
 macro_rules! append {
@@ -175,6 +920,13 @@ macro_rules! append {
     };
 }
 
+macro_rules! append_u16le {
+    ($iter:ident, $value:expr) => {
+        append!($iter, $value as u8);
+        append!($iter, ($value >> 8) as u8);
+    };
+}
+
 macro_rules! append_u24le {
     ($iter:ident, $value:expr) => {
         append!($iter, $value as u8);
@@ -183,230 +935,565 @@ macro_rules! append_u24le {
     };
 }
 
-impl<B: UsbBus, D: EndpointDirection> AudioStream<'_, B, D> {
-    fn write_ac_descriptors(&self, writer: &mut DescriptorWriter) -> usb_device::Result<()> {
-        let is_input = self.endpoint.address().direction() == UsbDirection::In;
-        let terminal_type: u16 = self.stream_config.terminal_type.into();
-        let id_offset = if is_input { 0 } else { 4 };
+impl<'a, B: UsbBus, D: EndpointDirection> AudioStream<'a, B, D> {
+    /// Create the state of a stream. `terminal_link` is the ID of the USB
+    /// streaming Terminal of the stream, `units` are the Units configured by
+    /// its `StreamConfig` and `cluster` is its logical channel cluster.
+    fn new(
+        stream_config: StreamConfig<'a>,
+        interface: InterfaceNumber,
+        endpoint: Endpoint<'a, B, D>,
+        feedback_endpoint: Option<Endpoint<'a, B, In>>,
+        terminal_link: u8,
+        units: StreamUnits,
+        cluster: Cluster,
+    ) -> Result<Self> {
+        let direction = endpoint.address().direction();
+        let sample_rate = stream_config.rates.max();
+        let feedback = match (feedback_endpoint, stream_config.feedback_refresh(direction)) {
+            (Some(endpoint), Some(refresh))
+                if (1..=9).contains(&refresh)
+                    && stream_config.sync_type(direction) == SyncType::Asynchronous =>
+            {
+                Some(FeedbackEndpoint {
+                    endpoint,
+                    refresh,
+                    value: FeedbackEndpoint::<B>::nominal_value(sample_rate),
+                })
+            }
+            (None, None) => None,
+            _ => return Err(Error::InvalidValue),
+        };
+        Ok(AudioStream {
+            stream_config,
+            interface,
+            endpoint,
+            alt_setting: DEFAULT_ALTERNATE_SETTING,
+            terminal_link,
+            units,
+            cluster,
+            sample_rate,
+            sample_rate_changed: false,
+            feedback,
+        })
+    }
 
-        // write Input Terminal Descriptor (12 bytes)
-        let tt = if is_input {
-            terminal_type
+    /// Name of a non-predefined channel of the stream identified by its
+    /// string index
+    fn channel_name(&self, index: u8) -> Option<&'a str> {
+        if self.cluster.names == 0 {
+            return None;
+        }
+        let ndx = index.checked_sub(self.cluster.names)?;
+        self.stream_config.channel_names.get(ndx as usize).copied()
+    }
+
+    /// Name of the Terminal or Unit `id` configured by the `StreamConfig`
+    fn entity_name(&self, id: u8) -> Option<&'a str> {
+        let config = &self.stream_config;
+        let units = &self.units;
+        let is = |entity: Option<Entity>| entity.map(Entity::id) == Some(id);
+        if is(units.terminal) {
+            return config.terminal_name;
+        }
+        let names = config.unit_names?;
+        if is(units.selector_unit) {
+            names.selector_unit
+        } else if is(units.extension_unit) {
+            names.extension_unit
+        } else if is(units.feature_unit) {
+            names.feature_unit
+        } else if is(units.mixer_unit) {
+            names.mixer_unit
         } else {
-            TerminalType::UsbStreaming.into()
-        }
-        .to_le_bytes();
-
-        // Calculate wChannelConfig based on channel count
-        let channel_config = match self.stream_config.channels {
-            1 => 0x0001u16, // L
-            2 => 0x0003u16, // L+R
-            4 => 0x0033u16, // L+R+LS+RS
-            6 => 0x003Fu16, // L+R+C+LFE+LS+RS
-            8 => 0x00FFu16, // L+R+C+LFE+LS+RS+LC+RC
-            _ => 0x0003u16, // Default to stereo for unsupported counts
-        };
+            let ndx = units.processing_units.iter().position(|&pu| is(pu))?;
+            names.processing_units.get(ndx).copied()
+        }
+    }
 
-        writer.write(
-            CS_INTERFACE,
-            &[
-                INPUT_TERMINAL,                // bDescriptorSubtype
-                ID_INPUT_TERMINAL + id_offset, // bTerminalID
-                tt[0],                         // wTerminalType
-                tt[1],
-                0x00,                        // bAssocTerminal
-                self.stream_config.channels, // bNrChannels
-                (channel_config & 0xFF) as u8,
-                (channel_config >> 8) as u8, // wChannelConfig
-                0x00, // iChannelNames
-                0x00, // iTerminal
-            ],
-        )?;
+    fn selector_unit(&self) -> Result<Entity> {
+        self.units.selector_unit.ok_or(Error::InvalidValue)
+    }
+
+    fn extension_unit(&self) -> Result<Entity> {
+        self.units.extension_unit.ok_or(Error::InvalidValue)
+    }
+
+    fn feature_unit(&self) -> Result<Entity> {
+        self.units.feature_unit.ok_or(Error::InvalidValue)
+    }
+
+    fn mixer_unit(&self) -> Result<Entity> {
+        self.units.mixer_unit.ok_or(Error::InvalidValue)
+    }
+
+    fn processing_unit(&self, unit: usize) -> Result<Entity> {
+        self.units
+            .processing_units
+            .get(unit)
+            .copied()
+            .flatten()
+            .ok_or(Error::InvalidValue)
+    }
 
-        // write Output Terminal Descriptor (9 bytes)
-        let tt = if is_input {
-            TerminalType::UsbStreaming.into()
+    /// Handle a GET request of an Endpoint Control. Returns the number of bytes
+    /// written to `data` or `None` if the request is not supported.
+    fn get_endpoint_control(&self, req: &control::Request, data: &mut [u8]) -> Option<usize> {
+        if req.request == GET_CUR && req.value >> 8 == SAMPLING_FREQ_CONTROL {
+            data[..3].copy_from_slice(&self.sample_rate.to_le_bytes()[..3]);
+            Some(3)
         } else {
-            terminal_type
-        }
-        .to_le_bytes();
-        writer.write(
-            CS_INTERFACE,
-            &[
-                OUTPUT_TERMINAL,                // bDescriptorSubtype
-                ID_OUTPUT_TERMINAL + id_offset, // bTerminalID
-                tt[0],                          // wTerminalType
-                tt[1],
-                0x00,                          // bAssocTerminal
-                ID_INPUT_TERMINAL + id_offset, // bSourceID
-                0x00,                          // iTerminal
-            ],
-        )
+            None
+        }
+    }
+
+    /// Handle a SET request of an Endpoint Control. Returns `false` if the
+    /// request is not supported.
+    fn set_endpoint_control(&mut self, req: &control::Request, data: &[u8]) -> bool {
+        if req.request != SET_CUR || req.value >> 8 != SAMPLING_FREQ_CONTROL || data.len() < 3 {
+            return false;
+        }
+        let rate = u32::from_le_bytes([data[0], data[1], data[2], 0]);
+        let supported = match self.stream_config.alt_config(self.alt_setting) {
+            Some(config) => config.rates.contains(rate),
+            None => self
+                .stream_config
+                .alt_configs()
+                .any(|config| config.rates.contains(rate)),
+        };
+        if !supported {
+            return false;
+        }
+        if rate != self.sample_rate {
+            self.sample_rate = rate;
+            self.sample_rate_changed = true;
+            if let Some(ref mut fb) = self.feedback {
+                fb.value = FeedbackEndpoint::<B>::nominal_value(rate);
+            }
+        }
+        true
+    }
+
+    /// Select an Alternate Setting. Returns `false` if the Alternate Setting
+    /// does not exist.
+    fn set_alt_setting(&mut self, alt_setting: u16) -> bool {
+        let valid = alt_setting == DEFAULT_ALTERNATE_SETTING as u16
+            || u8::try_from(alt_setting)
+                .is_ok_and(|alt| self.stream_config.alt_config(alt).is_some());
+        if valid {
+            self.alt_setting = alt_setting as u8;
+        }
+        valid
     }
 
     fn write_as_and_ep_descriptors(&self, writer: &mut DescriptorWriter) -> usb_device::Result<()> {
-        let is_input = self.endpoint.address().direction() == UsbDirection::In;
-        let id_offset = if is_input { 0 } else { 4 };
         // Standard AS Interface Descriptor (Alt. Set. 0)
         writer.interface(self.interface, AUDIO, AUDIOSTREAMING, 0x00)?;
 
-        // Standard AS Interface Descriptor (Alt. Set. 1)
-        writer.interface_alt(self.interface, 0x01, AUDIO, AUDIOSTREAMING, 0x00, None)?;
+        let num_endpoints = if self.feedback.is_some() { 2 } else { 1 };
+        for (ndx, config) in self.stream_config.alt_configs().enumerate() {
+            // Standard AS Interface Descriptor (Alt. Set. 1, 2, ...). The
+            // descriptor is written directly since the endpoint sizes differ
+            // between the alternate settings.
+            writer.write(
+                descriptor_type::INTERFACE,
+                &[
+                    self.interface.into(), // bInterfaceNumber
+                    ndx as u8 + 1,         // bAlternateSetting
+                    num_endpoints,         // bNumEndpoints
+                    AUDIO,                 // bInterfaceClass
+                    AUDIOSTREAMING,        // bInterfaceSubClass
+                    0x00,                  // bInterfaceProtocol
+                    0x00,                  // iInterface
+                ],
+            )?;
 
-        // Class-specific AS General Interface Descriptor
-        let terminal_link = id_offset
-            + if is_input {
-                ID_OUTPUT_TERMINAL
-            } else {
-                ID_INPUT_TERMINAL
-            };
-        writer.write(
-            CS_INTERFACE,
-            &[
-                AS_GENERAL,    // bDescriptorSubtype:
-                terminal_link, // bTerminalLink
-                0x01,          // bDelay
-                PCM as u8,
-                (PCM >> 8) as u8, // wFormatTag
-            ],
-        )?;
+            // Class-specific AS General Interface Descriptor
+            let format_tag = config.format.format_tag();
+            writer.write(
+                CS_INTERFACE,
+                &[
+                    AS_GENERAL,         // bDescriptorSubtype:
+                    self.terminal_link, // bTerminalLink
+                    0x01,               // bDelay
+                    format_tag as u8,
+                    (format_tag >> 8) as u8, // wFormatTag
+                ],
+            )?;
 
-        // Type 1 Format Type Descriptor
-        let mut format_desc = [0x00u8; 128];
-        let mut iter = format_desc.iter_mut().enumerate();
-        append!(iter, FORMAT_TYPE); // bDescriptorSubtype;
-        append!(iter, FORMAT_TYPE_I); // bFormatType
-        append!(iter, self.stream_config.channels); // bNrChannels
-        append!(
-            iter,
-            match self.stream_config.format {
-                // bSubFrameSize
-                Format::S16le => 2,
-                Format::S24le => 3,
-            }
-        );
-        append!(
-            iter,
-            match self.stream_config.format {
-                // bBitResolution
-                Format::S16le => 16,
-                Format::S24le => 24,
+            // Type I, Type II or Type III Format Type Descriptor
+            let mut format_desc = [0x00u8; 128];
+            let mut iter = format_desc.iter_mut().enumerate();
+            append!(iter, FORMAT_TYPE); // bDescriptorSubtype;
+            append!(iter, config.format.format_type()); // bFormatType
+            if config.format.format_type() == FORMAT_TYPE_II {
+                append_u16le!(iter, config.max_bit_rate); // wMaxBitRate
+                append_u16le!(iter, config.samples_per_frame); // wSamplesPerFrame
+            } else {
+                append!(iter, config.channels); // bNrChannels
+                append!(iter, config.format.subframe_size()); // bSubFrameSize
+                append!(iter, config.format.bit_resolution()); // bBitResolution
             }
-        );
-        match self.stream_config.rates {
-            Rates::Continuous(min, max) => {
-                append!(iter, 0x00); // bSamFreqType
-                append_u24le!(iter, min);
-                append_u24le!(iter, max);
+            match config.rates {
+                Rates::Continuous(min, max) => {
+                    append!(iter, 0x00); // bSamFreqType
+                    append_u24le!(iter, min);
+                    append_u24le!(iter, max);
+                }
+                Rates::Discrete(rates) => {
+                    append!(iter, rates.len() as u8); // bSamFreqType
+                    for rate in rates {
+                        append_u24le!(iter, *rate);
+                    }
+                }
             }
-            Rates::Discrete(rates) => {
-                append!(iter, rates.len() as u8); // bSamFreqType
-                for rate in rates {
-                    append_u24le!(iter, *rate);
+            let length = iter.next().unwrap().0;
+            writer.write(CS_INTERFACE, &format_desc[..length])?;
+
+            // Format-Specific Descriptor
+            let [tag_lo, tag_hi] = format_tag.to_le_bytes();
+            match config.format {
+                Format::Mpeg {
+                    capabilities,
+                    features,
+                } => {
+                    let [cap_lo, cap_hi] = capabilities.to_le_bytes();
+                    writer.write(
+                        CS_INTERFACE,
+                        &[
+                            FORMAT_SPECIFIC, // bDescriptorSubtype
+                            tag_lo,
+                            tag_hi, // wFormatTag
+                            cap_lo,
+                            cap_hi,   // bmMPEGCapabilities
+                            features, // bmMPEGFeatures
+                        ],
+                    )?;
                 }
+                Format::Ac3 { bsid, features } => {
+                    let bsid = bsid.to_le_bytes();
+                    writer.write(
+                        CS_INTERFACE,
+                        &[
+                            FORMAT_SPECIFIC, // bDescriptorSubtype
+                            tag_lo,
+                            tag_hi, // wFormatTag
+                            bsid[0],
+                            bsid[1],
+                            bsid[2],
+                            bsid[3],  // bmBSID
+                            features, // bmAC3Features
+                        ],
+                    )?;
+                }
+                _ => {}
             }
-        }
-        let length = iter.next().unwrap().0;
-        writer.write(CS_INTERFACE, &format_desc[..length])?;
 
-        // Standard Endpoint Descriptor
-        writer.endpoint(&self.endpoint)?;
+            // Standard AS Isochronous Audio Data Endpoint Descriptor
+            let synch_address = self
+                .feedback
+                .as_ref()
+                .map_or(0, |fb| fb.endpoint.address().into());
+            write_endpoint(writer, &self.endpoint, config.ep_size, 0x00, synch_address)?;
 
-        // Class-specific Isoc. Audio Data Endpoint Descriptor
-        writer.write(
-            0x25,
-            &[
-                // bDescriptorType: CS_ENDPOINT
-                0x01, // bDescriptorSubtype: GENERAL
-                0x00, // bmAttributes
-                0x00, // bLockDelayUnits
-                0x00, 0x00, // wLockDelay
-            ],
-        )
+            // Class-specific Isoc. Audio Data Endpoint Descriptor
+            let (lock_delay_units, lock_delay) = match self.stream_config.lock_delay {
+                None => (0x00, 0u16),
+                Some(LockDelay::Milliseconds(delay)) => (0x01, delay),
+                Some(LockDelay::DecodedSamples(delay)) => (0x02, delay),
+            };
+            writer.write(
+                0x25,
+                &[
+                    // bDescriptorType: CS_ENDPOINT
+                    0x01,             // bDescriptorSubtype: GENERAL
+                    0x01,             // bmAttributes: Sampling Frequency control
+                    lock_delay_units, // bLockDelayUnits
+                    lock_delay as u8,
+                    (lock_delay >> 8) as u8, // wLockDelay
+                ],
+            )?;
+
+            // Standard AS Isochronous Synch Endpoint Descriptor
+            if let Some(ref fb) = self.feedback {
+                write_endpoint(writer, &fb.endpoint, FEEDBACK_SIZE, fb.refresh, 0x00)?;
+            }
+        }
+        Ok(())
     }
 }
 
 /// Builder class to create an `AudioClass` structure.
-pub struct AudioClassBuilder<'a> {
-    input: Option<StreamConfig<'a>>,
-    output: Option<StreamConfig<'a>>,
+///
+/// The `AudioClass` keeps the states of up to `U` Units, i.e. the Units
+/// configured by the `StreamConfig`s or the Units of the `Topology`. `U`
+/// defaults to 0 and is set by means of `max_units`.
+pub struct AudioClassBuilder<'a, const U: usize = 0> {
+    inputs: [Option<StreamConfig<'a>>; MAX_STREAMS],
+    outputs: [Option<StreamConfig<'a>>; MAX_STREAMS],
+    too_many_streams: bool,
+    topology: Option<&'a Topology<'a>>,
+    status_interval: Option<u8>,
+    function_name: Option<&'a str>,
+}
+
+/// Put a value into the first free slot. Returns `false` if all slots are
+/// occupied.
+fn push<T>(slots: &mut [Option<T>], value: T) -> bool {
+    match slots.iter_mut().find(|slot| slot.is_none()) {
+        Some(slot) => {
+            *slot = Some(value);
+            true
+        }
+        None => false,
+    }
+}
+
+impl Default for AudioClassBuilder<'static> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<'a> AudioClassBuilder<'a> {
+impl AudioClassBuilder<'static> {
     /// Create a new AudioClassBuilder
     pub fn new() -> AudioClassBuilder<'static> {
         AudioClassBuilder {
-            input: None,
-            output: None,
+            inputs: Default::default(),
+            outputs: Default::default(),
+            too_many_streams: false,
+            topology: None,
+            status_interval: None,
+            function_name: None,
         }
     }
+}
 
-    /// Configure the input audio stream according to a `StreamConfig`.
-    /// At most one input stream can be configured. When calling this method
-    /// multiple times, the last call matters.
-    pub fn input(self, input: StreamConfig<'a>) -> AudioClassBuilder<'a> {
+impl<'a, const U: usize> AudioClassBuilder<'a, U> {
+    /// Reserve room for the states of up to `M` Units in the `AudioClass`,
+    /// e.g. `max_units::<2>()` for a stream with a Feature Unit and a Mixer
+    /// Unit. Building an `AudioClass` with more Units fails.
+    pub fn max_units<const M: usize>(self) -> AudioClassBuilder<'a, M> {
         AudioClassBuilder {
-            input: Some(input),
-            output: self.output,
+            inputs: self.inputs,
+            outputs: self.outputs,
+            too_many_streams: self.too_many_streams,
+            topology: self.topology,
+            status_interval: self.status_interval,
+            function_name: self.function_name,
         }
     }
 
-    /// Configure the output audio stream according to a `StreamConfig`.
-    /// At most one output stream can be configured. When calling this method
-    /// multiple times, the last call matters.
-    pub fn output(self, output: StreamConfig<'a>) -> AudioClassBuilder<'a> {
+    /// Add an input audio stream configured according to a `StreamConfig`.
+    /// Up to `MAX_STREAMS` input streams can be added. The input streams are
+    /// numbered in the order of the calls of this method starting with 0.
+    pub fn input(mut self, input: StreamConfig<'a>) -> AudioClassBuilder<'a, U> {
+        self.too_many_streams |= !push(&mut self.inputs, input);
+        self
+    }
+
+    /// Add an output audio stream configured according to a `StreamConfig`.
+    /// Up to `MAX_STREAMS` output streams can be added. The output streams are
+    /// numbered in the order of the calls of this method starting with 0.
+    pub fn output(mut self, output: StreamConfig<'a>) -> AudioClassBuilder<'a, U> {
+        self.too_many_streams |= !push(&mut self.outputs, output);
+        self
+    }
+
+    /// Use a `Topology` of Terminals and Units instead of the Terminals and
+    /// Units configured by the `StreamConfig`s. The `StreamConfig`s must not
+    /// configure any Units and their terminal types are ignored. The channel
+    /// configuration of an output stream applies to its USB streaming Input
+    /// Terminal whereas that of an input stream is ignored. The topology is
+    /// borrowed by the `AudioClass`, which needs room for the states of its
+    /// Units, see `max_units`.
+    pub fn topology(self, topology: &'a Topology<'a>) -> AudioClassBuilder<'a, U> {
         AudioClassBuilder {
-            input: self.input,
-            output: Some(output),
+            topology: Some(topology),
+            ..self
         }
     }
 
-    /// Create the `AudioClass` structure
-    pub fn build<B: UsbBus>(self, alloc: &'a UsbBusAllocator<B>) -> Result<AudioClass<'a, B>> {
-        let control_iface = alloc.interface();
+    /// Add an interrupt IN endpoint to the AudioControl interface that is
+    /// polled by the host every `interval` ms. The endpoint is used to notify
+    /// the host of changes of controls made by the device. See
+    /// `AudioClass::notify_control_changed`.
+    pub fn status_endpoint(self, interval: u8) -> AudioClassBuilder<'a, U> {
+        AudioClassBuilder {
+            status_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Name the audio function. The name is provided as string descriptor
+    /// (iFunction of the Interface Association Descriptor) and typically
+    /// shown by the host as the name of the audio device.
+    pub fn function_name(self, name: &'a str) -> AudioClassBuilder<'a, U> {
+        AudioClassBuilder {
+            function_name: Some(name),
+            ..self
+        }
+    }
+
+    /// Create the `AudioClass` structure. Returns an error if more than
+    /// `MAX_STREAMS` streams have been added in a direction, if the topology
+    /// is not valid or if it has more than `U` Units. Requests not handled by
+    /// the `AudioClass` itself are rejected.
+    pub fn build<B: UsbBus>(
+        self,
+        alloc: &'a UsbBusAllocator<B>,
+    ) -> Result<AudioClass<'a, B, (), U>> {
+        self.build_with_handler(alloc, ())
+    }
+
+    /// Create the `AudioClass` structure with a handler of the class-specific
+    /// requests not handled by the `AudioClass` itself. Returns an error if
+    /// more than `MAX_STREAMS` streams have been added in a direction, if the
+    /// topology is not valid or if it has more than `U` Units.
+    pub fn build_with_handler<B: UsbBus, H: AudioControlHandler>(
+        self,
+        alloc: &'a UsbBusAllocator<B>,
+        handler: H,
+    ) -> Result<AudioClass<'a, B, H, U>> {
+        if self.too_many_streams {
+            return Err(Error::InvalidValue);
+        }
+        let mut input_clusters = [Cluster::default(); MAX_STREAMS];
+        let mut num_inputs = 0;
+        for (cluster, config) in input_clusters.iter_mut().zip(self.inputs.iter().flatten()) {
+            *cluster = config.cluster(alloc);
+            num_inputs += 1;
+        }
+        let mut output_clusters = [Cluster::default(); MAX_STREAMS];
+        let mut num_outputs = 0;
+        for (cluster, config) in output_clusters
+            .iter_mut()
+            .zip(self.outputs.iter().flatten())
+        {
+            *cluster = config.cluster(alloc);
+            num_outputs += 1;
+        }
+        let mut input_units = [StreamUnits::default(); MAX_STREAMS];
+        let mut output_units = [StreamUnits::default(); MAX_STREAMS];
+        let generated;
+        let topology = match self.topology {
+            Some(topology) => {
+                let configs = self.inputs.iter().chain(self.outputs.iter()).flatten();
+                if configs.clone().any(StreamConfig::has_units) {
+                    return Err(Error::InvalidValue);
+                }
+                topology
+            }
+            None => {
+                // generate the Terminals and Units configured by the streams
+                let mut topology = Topology::new();
+                for (ndx, config) in self.inputs.iter().flatten().enumerate() {
+                    let cluster = input_clusters[ndx];
+                    input_units[ndx] =
+                        topology.add_stream(config, UsbDirection::In, ndx, cluster)?;
+                }
+                for (ndx, config) in self.outputs.iter().flatten().enumerate() {
+                    let cluster = output_clusters[ndx];
+                    output_units[ndx] =
+                        topology.add_stream(config, UsbDirection::Out, ndx, cluster)?;
+                }
+                let inputs = self.inputs.iter().flatten().zip(&input_units);
+                let outputs = self.outputs.iter().flatten().zip(&output_units);
+                for (config, units) in inputs.chain(outputs) {
+                    topology.connect_stream_sources(config, units, &input_units, &output_units)?;
+                }
+                generated = topology;
+                &generated
+            }
+        };
+        let mut entities = topology.build(
+            &input_clusters[..num_inputs],
+            &output_clusters[..num_outputs],
+        )?;
+        for id in topology.names() {
+            entities.set_name(id, alloc.string().into());
+        }
+        let function_name = self.function_name.map(|name| (alloc.string(), name));
+
+        let control_iface = alloc.interface();
         let mut ac = AudioClass {
             control_iface,
-            input: None,
-            output: None,
+            inputs: Default::default(),
+            outputs: Default::default(),
+            entities,
+            topology: self.topology,
+            status: None,
+            function_name,
+            handler,
         };
-        if let Some(stream_config) = self.input {
+        for (ndx, stream_config) in self.inputs.into_iter().flatten().enumerate() {
             let interface = alloc.interface();
             let endpoint = alloc.alloc(
                 None,
                 EndpointType::Isochronous {
-                    synchronization: IsochronousSynchronizationType::Asynchronous,
+                    synchronization: stream_config.sync_type(UsbDirection::In).into(),
                     usage: IsochronousUsageType::Data,
                 },
-                stream_config.ep_size,
+                stream_config.max_ep_size(),
                 1,
             )?;
-            let alt_setting = DEFAULT_ALTERNATE_SETTING;
-            ac.input = Some(AudioStream {
+            let terminal_link = topology.streaming_terminal(UsbDirection::In, ndx)?.id();
+            let stream = AudioStream::new(
                 stream_config,
                 interface,
                 endpoint,
-                alt_setting,
-            })
+                None,
+                terminal_link,
+                input_units[ndx],
+                input_clusters[ndx],
+            )?;
+            ac.inputs[ndx] = Some(stream);
         }
 
-        if let Some(stream_config) = self.output {
+        for (ndx, stream_config) in self.outputs.into_iter().flatten().enumerate() {
             let interface = alloc.interface();
             let endpoint = alloc.alloc(
                 None,
                 EndpointType::Isochronous {
-                    synchronization: IsochronousSynchronizationType::Adaptive,
+                    synchronization: stream_config.sync_type(UsbDirection::Out).into(),
                     usage: IsochronousUsageType::Data,
                 },
-                stream_config.ep_size,
+                stream_config.max_ep_size(),
                 1,
             )?;
-            let alt_setting = DEFAULT_ALTERNATE_SETTING;
-            ac.output = Some(AudioStream {
+            let feedback_endpoint = match stream_config.feedback_refresh(UsbDirection::Out) {
+                Some(_) => Some(alloc.alloc(
+                    None,
+                    EndpointType::Isochronous {
+                        synchronization: IsochronousSynchronizationType::NoSynchronization,
+                        usage: IsochronousUsageType::Feedback,
+                    },
+                    FEEDBACK_SIZE,
+                    1,
+                )?),
+                None => None,
+            };
+            let terminal_link = topology.streaming_terminal(UsbDirection::Out, ndx)?.id();
+            let stream = AudioStream::new(
                 stream_config,
                 interface,
                 endpoint,
-                alt_setting,
-            })
+                feedback_endpoint,
+                terminal_link,
+                output_units[ndx],
+                output_clusters[ndx],
+            )?;
+            ac.outputs[ndx] = Some(stream);
+        }
+
+        if let Some(interval) = self.status_interval {
+            let endpoint = alloc.alloc(None, EndpointType::Interrupt, STATUS_SIZE, interval)?;
+            ac.status = Some(StatusEndpoint {
+                endpoint,
+                queued: 0,
+                pending: 0,
+                busy: false,
+            });
         }
 
         Ok(ac)
@@ -416,142 +1503,1023 @@ impl<'a> AudioClassBuilder<'a> {
 /// USB device class for audio devices.
 ///
 /// This device class based on the "Universal Serial Bus Device Class Definition
-/// for Audio Devices", Release 1.0. It supports one input stream and/or one
-/// output stream.
-pub struct AudioClass<'a, B: UsbBus> {
+/// for Audio Devices", Release 1.0. It supports up to `MAX_STREAMS` input
+/// streams and up to `MAX_STREAMS` output streams. Methods accessing a certain
+/// stream take the number of the stream as assigned by `AudioClassBuilder`.
+/// The methods without a stream number access the first stream (number 0).
+pub struct AudioClass<'a, B: UsbBus, H = (), const U: usize = 0> {
     control_iface: InterfaceNumber,
-    input: Option<AudioStream<'a, B, In>>,
-    output: Option<AudioStream<'a, B, Out>>,
+    inputs: [Option<AudioStream<'a, B, In>>; MAX_STREAMS],
+    outputs: [Option<AudioStream<'a, B, Out>>; MAX_STREAMS],
+    /// States of the Terminals and Units including up to `U` Units
+    entities: Entities<U>,
+    /// Topology passed to `AudioClassBuilder::topology`
+    topology: Option<&'a Topology<'a>>,
+    status: Option<StatusEndpoint<'a, B>>,
+    /// String index and name of the audio function
+    function_name: Option<(StringIndex, &'a str)>,
+    handler: H,
 }
 
-impl<B: UsbBus> AudioClass<'_, B> {
+impl<'a, B: UsbBus, H: AudioControlHandler, const U: usize> AudioClass<'a, B, H, U> {
+    fn input(&self, stream: usize) -> Result<&AudioStream<'a, B, In>> {
+        self.inputs
+            .get(stream)
+            .and_then(Option::as_ref)
+            .ok_or(Error::StreamNotInitialized)
+    }
+
+    fn input_mut(&mut self, stream: usize) -> Result<&mut AudioStream<'a, B, In>> {
+        self.inputs
+            .get_mut(stream)
+            .and_then(Option::as_mut)
+            .ok_or(Error::StreamNotInitialized)
+    }
+
+    fn output(&self, stream: usize) -> Result<&AudioStream<'a, B, Out>> {
+        self.outputs
+            .get(stream)
+            .and_then(Option::as_ref)
+            .ok_or(Error::StreamNotInitialized)
+    }
+
+    fn output_mut(&mut self, stream: usize) -> Result<&mut AudioStream<'a, B, Out>> {
+        self.outputs
+            .get_mut(stream)
+            .and_then(Option::as_mut)
+            .ok_or(Error::StreamNotInitialized)
+    }
+
     /// Read audio frames as output by the host. Returns an Error if no output
     /// stream has been configured.
     pub fn read(&self, data: &mut [u8]) -> Result<usize> {
-        if let Some(ref info) = self.output {
-            info.endpoint.read(data).map_err(Error::UsbError)
-        } else {
-            Err(Error::StreamNotInitialized)
-        }
+        self.read_stream(0, data)
+    }
+
+    /// Read audio frames of an output stream as output by the host. Returns an
+    /// Error if the output stream has not been configured.
+    pub fn read_stream(&self, stream: usize, data: &mut [u8]) -> Result<usize> {
+        self.output(stream)?
+            .endpoint
+            .read(data)
+            .map_err(Error::UsbError)
     }
 
     /// Write audio frames to be input by the host. Returns an Error when no
     /// input stream has been configured.
     pub fn write(&self, data: &[u8]) -> Result<usize> {
-        if let Some(ref info) = self.input {
-            info.endpoint.write(data).map_err(Error::UsbError)
-        } else {
-            Err(Error::StreamNotInitialized)
+        self.write_stream(0, data)
+    }
+
+    /// Write audio frames of an input stream to be input by the host. Returns
+    /// an Error when the input stream has not been configured.
+    pub fn write_stream(&self, stream: usize, data: &[u8]) -> Result<usize> {
+        self.input(stream)?
+            .endpoint
+            .write(data)
+            .map_err(Error::UsbError)
+    }
+
+    /// Read audio frames of samples of type `S` as output by the host. See
+    /// `read_stream_frames`.
+    pub fn read_frames<S: Sample, const N: usize>(&self, frames: &mut [[S; N]]) -> Result<usize> {
+        self.read_stream_frames(0, frames)
+    }
+
+    /// Read audio frames of an output stream as output by the host and unpack
+    /// them into `frames`. `S` must match the format and `N` must match the
+    /// number of channels of the active Alternate Setting. Returns the number
+    /// of audio frames read. Returns an error if the output stream has not
+    /// been configured, Alternate Setting 0 is active, the format or the
+    /// number of channels does not match, `frames` cannot hold a packet of
+    /// the maximum packet size or the packet contains a partial audio frame.
+    pub fn read_stream_frames<S: Sample, const N: usize>(
+        &self,
+        stream: usize,
+        frames: &mut [[S; N]],
+    ) -> Result<usize> {
+        let mut buf = [0u8; MAX_ISO_EP_SIZE as usize];
+        let (len, subframe_size) = self.read_packet::<S>(stream, N, frames.len(), &mut buf)?;
+        let frame_size = N * subframe_size;
+        let num_frames = len / frame_size;
+        for (frame, data) in frames.iter_mut().zip(buf[..len].chunks_exact(frame_size)) {
+            *frame = unpack_frame(data, subframe_size);
+        }
+        Ok(num_frames)
+    }
+
+    /// Read audio frames as output by the host into a FIFO. See
+    /// `read_stream_fifo`.
+    #[cfg(target_has_atomic = "ptr")]
+    pub fn read_fifo<S: Sample, const C: usize, const N: usize>(
+        &self,
+        producer: &mut fifo::Producer<S, C, N>,
+    ) -> Result<usize> {
+        self.read_stream_fifo(0, producer)
+    }
+
+    /// Read audio frames of an output stream as output by the host and append
+    /// them to a FIFO. If the FIFO is full, its oldest frames are dropped.
+    /// Returns the number of audio frames read. Returns an error under the
+    /// conditions listed at `read_stream_frames` except for the size of the
+    /// packet.
+    #[cfg(target_has_atomic = "ptr")]
+    pub fn read_stream_fifo<S: Sample, const C: usize, const N: usize>(
+        &self,
+        stream: usize,
+        producer: &mut fifo::Producer<S, C, N>,
+    ) -> Result<usize> {
+        let mut buf = [0u8; MAX_ISO_EP_SIZE as usize];
+        let (len, subframe_size) = self.read_packet::<S>(stream, C, usize::MAX, &mut buf)?;
+        let frames = buf[..len]
+            .chunks_exact(C * subframe_size)
+            .map(|data| unpack_frame(data, subframe_size));
+        Ok(producer.push_iter(frames))
+    }
+
+    /// Read a packet of an output stream having audio frames of `channels`
+    /// samples of type `S` unless a packet of the maximum packet size could
+    /// exceed `max_frames` audio frames. Returns the length of the packet and
+    /// the subframe size.
+    fn read_packet<S: Sample>(
+        &self,
+        stream: usize,
+        channels: usize,
+        max_frames: usize,
+        buf: &mut [u8],
+    ) -> Result<(usize, usize)> {
+        let so = self.output(stream)?;
+        let config = so
+            .stream_config
+            .alt_config(so.alt_setting)
+            .ok_or(Error::StreamNotInitialized)?;
+        let subframe_size = config.sample_subframe_size::<S>(channels)?;
+        // check before reading such that the packet is not lost
+        if max_frames.saturating_mul(channels * subframe_size) < config.ep_size as usize {
+            return Err(Error::UsbError(UsbError::BufferOverflow));
+        }
+        let len = so.endpoint.read(buf)?;
+        if len % (channels * subframe_size) != 0 {
+            return Err(Error::InvalidValue);
         }
+        Ok((len, subframe_size))
+    }
+
+    /// Write audio frames of samples of type `S` to be input by the host. See
+    /// `write_stream_frames`.
+    pub fn write_frames<S: Sample, const N: usize>(&self, frames: &[[S; N]]) -> Result<usize> {
+        self.write_stream_frames(0, frames)
+    }
+
+    /// Pack the audio frames `frames` and write them as one packet of an input
+    /// stream to be input by the host. `S` must match the format and `N` must
+    /// match the number of channels of the active Alternate Setting. Returns
+    /// the number of audio frames written. Returns an error if the input
+    /// stream has not been configured, Alternate Setting 0 is active, the
+    /// format or the number of channels does not match or the packet exceeds
+    /// the maximum packet size.
+    pub fn write_stream_frames<S: Sample, const N: usize>(
+        &self,
+        stream: usize,
+        frames: &[[S; N]],
+    ) -> Result<usize> {
+        let si = self.input(stream)?;
+        let config = si
+            .stream_config
+            .alt_config(si.alt_setting)
+            .ok_or(Error::StreamNotInitialized)?;
+        let subframe_size = config.sample_subframe_size::<S>(N)?;
+        let frame_size = N * subframe_size;
+        let len = frames.len() * frame_size;
+        if len > config.ep_size as usize {
+            return Err(Error::BandwidthExceeded);
+        }
+        let mut buf = [0u8; MAX_ISO_EP_SIZE as usize];
+        for (frame, data) in frames.iter().zip(buf[..len].chunks_exact_mut(frame_size)) {
+            pack_frame(frame, data, subframe_size);
+        }
+        si.endpoint.write(&buf[..len])?;
+        Ok(frames.len())
+    }
+
+    /// Write audio frames to be input by the host from a FIFO. See
+    /// `write_stream_fifo`.
+    #[cfg(target_has_atomic = "ptr")]
+    pub fn write_fifo<S: Sample, const C: usize, const N: usize>(
+        &self,
+        consumer: &mut fifo::Consumer<S, C, N>,
+        frames: usize,
+    ) -> Result<usize> {
+        self.write_stream_fifo(0, consumer, frames)
+    }
+
+    /// Take `frames` audio frames from a FIFO and write them as one packet of
+    /// an input stream to be input by the host, e.g. the number of frames
+    /// determined by a `PacketScheduler`. If the FIFO holds less frames, the
+    /// packet is filled up with silence. Returns the number of audio frames
+    /// written. Returns an error under the conditions listed at
+    /// `write_stream_frames` without taking frames from the FIFO. The frames
+    /// are lost if the endpoint is not ready to send the packet.
+    #[cfg(target_has_atomic = "ptr")]
+    pub fn write_stream_fifo<S: Sample, const C: usize, const N: usize>(
+        &self,
+        stream: usize,
+        consumer: &mut fifo::Consumer<S, C, N>,
+        frames: usize,
+    ) -> Result<usize> {
+        let si = self.input(stream)?;
+        let config = si
+            .stream_config
+            .alt_config(si.alt_setting)
+            .ok_or(Error::StreamNotInitialized)?;
+        let subframe_size = config.sample_subframe_size::<S>(C)?;
+        let frame_size = C * subframe_size;
+        let len = frames.saturating_mul(frame_size);
+        if len > config.ep_size as usize {
+            return Err(Error::BandwidthExceeded);
+        }
+        let mut buf = [0u8; MAX_ISO_EP_SIZE as usize];
+        let mut data = buf[..len].chunks_exact_mut(frame_size);
+        consumer.pop_with(frames, |frame| {
+            if let Some(data) = data.next() {
+                pack_frame(&frame, data, subframe_size);
+            }
+        });
+        si.endpoint.write(&buf[..len])?;
+        Ok(frames)
     }
 
     /// Get current Alternate Setting of the input stream. Returns an error if
     /// the stream is not configured.
     pub fn input_alt_setting(&self) -> Result<u8> {
-        self.input
-            .as_ref()
-            .ok_or(Error::StreamNotInitialized)
-            .map(|si| si.alt_setting)
+        self.input_stream_alt_setting(0)
+    }
+
+    /// Get current Alternate Setting of an input stream. Returns an error if
+    /// the stream is not configured.
+    pub fn input_stream_alt_setting(&self, stream: usize) -> Result<u8> {
+        self.input(stream).map(|si| si.alt_setting)
     }
 
     /// Get current Alternate Setting of the output stream. Returns an error if
     /// the stream is not configured.
     pub fn output_alt_setting(&self) -> Result<u8> {
-        self.output
-            .as_ref()
-            .ok_or(Error::StreamNotInitialized)
-            .map(|si| si.alt_setting)
+        self.output_stream_alt_setting(0)
+    }
+
+    /// Get current Alternate Setting of an output stream. Returns an error if
+    /// the stream is not configured.
+    pub fn output_stream_alt_setting(&self, stream: usize) -> Result<u8> {
+        self.output(stream).map(|si| si.alt_setting)
+    }
+
+    /// Get the stream configuration of the currently active Alternate Setting
+    /// of the input stream. See `input_stream_config`.
+    pub fn input_config(&self) -> Result<Option<&StreamConfig<'a>>> {
+        self.input_stream_config(0)
+    }
+
+    /// Get the stream configuration of the currently active Alternate Setting
+    /// of an input stream, i.e. the stream configuration passed to
+    /// `AudioClassBuilder::input` for Alternate Setting 1 or one of its
+    /// alternates. Returns `None` if Alternate Setting 0 (zero bandwidth) is
+    /// active and an error if the stream is not configured.
+    pub fn input_stream_config(&self, stream: usize) -> Result<Option<&StreamConfig<'a>>> {
+        self.input(stream)
+            .map(|si| si.stream_config.alt_config(si.alt_setting))
+    }
+
+    /// Get the stream configuration of the currently active Alternate Setting
+    /// of the output stream. See `output_stream_config`.
+    pub fn output_config(&self) -> Result<Option<&StreamConfig<'a>>> {
+        self.output_stream_config(0)
+    }
+
+    /// Get the stream configuration of the currently active Alternate Setting
+    /// of an output stream, i.e. the stream configuration passed to
+    /// `AudioClassBuilder::output` for Alternate Setting 1 or one of its
+    /// alternates. Returns `None` if Alternate Setting 0 (zero bandwidth) is
+    /// active and an error if the stream is not configured.
+    pub fn output_stream_config(&self, stream: usize) -> Result<Option<&StreamConfig<'a>>> {
+        self.output(stream)
+            .map(|si| si.stream_config.alt_config(si.alt_setting))
+    }
+
+    /// Get the state of the Mute Control of a channel of the input stream's
+    /// Feature Unit. See `input_stream_mute`.
+    pub fn input_mute(&self, channel: u8) -> Result<bool> {
+        self.input_stream_mute(0, channel)
+    }
+
+    /// Get the state of the Mute Control of a channel of an input stream's
+    /// Feature Unit. Channel 0 is the master channel. Returns an error if the
+    /// stream is not configured or the stream has no such channel.
+    pub fn input_stream_mute(&self, stream: usize, channel: u8) -> Result<bool> {
+        self.feature_unit_mute(self.input(stream)?.feature_unit()?, channel)
+    }
+
+    /// Get the value of the Volume Control of a channel of the input stream's
+    /// Feature Unit. See `input_stream_volume`.
+    pub fn input_volume(&self, channel: u8) -> Result<i16> {
+        self.input_stream_volume(0, channel)
+    }
+
+    /// Get the value of the Volume Control of a channel of an input stream's
+    /// Feature Unit in units of 1/256 dB. Channel 0 is the master channel.
+    /// Returns an error if the stream is not configured or the stream has no
+    /// such channel.
+    pub fn input_stream_volume(&self, stream: usize, channel: u8) -> Result<i16> {
+        self.feature_unit_volume(self.input(stream)?.feature_unit()?, channel)
+    }
+
+    /// Get the state of the Mute Control of a channel of the output stream's
+    /// Feature Unit. See `output_stream_mute`.
+    pub fn output_mute(&self, channel: u8) -> Result<bool> {
+        self.output_stream_mute(0, channel)
+    }
+
+    /// Get the state of the Mute Control of a channel of an output stream's
+    /// Feature Unit. Channel 0 is the master channel. Returns an error if the
+    /// stream is not configured or the stream has no such channel.
+    pub fn output_stream_mute(&self, stream: usize, channel: u8) -> Result<bool> {
+        self.feature_unit_mute(self.output(stream)?.feature_unit()?, channel)
+    }
+
+    /// Get the value of the Volume Control of a channel of the output stream's
+    /// Feature Unit. See `output_stream_volume`.
+    pub fn output_volume(&self, channel: u8) -> Result<i16> {
+        self.output_stream_volume(0, channel)
+    }
+
+    /// Get the value of the Volume Control of a channel of an output stream's
+    /// Feature Unit in units of 1/256 dB. Channel 0 is the master channel.
+    /// Returns an error if the stream is not configured or the stream has no
+    /// such channel.
+    pub fn output_stream_volume(&self, stream: usize, channel: u8) -> Result<i16> {
+        self.feature_unit_volume(self.output(stream)?.feature_unit()?, channel)
+    }
+
+    /// Get the gain of a crosspoint of the input stream's Mixer Unit. See
+    /// `input_stream_mixer_gain`.
+    pub fn input_mixer_gain(&self, input_channel: u8, output_channel: u8) -> Result<i16> {
+        self.input_stream_mixer_gain(0, input_channel, output_channel)
+    }
+
+    /// Get the gain of a crosspoint of an input stream's Mixer Unit in units
+    /// of 1/256 dB. The input channels (1, 2, ...) are numbered consecutively
+    /// across the stream itself and the sources of the Mixer Unit; the output
+    /// channels (1, 2, ...) are the channels of the stream. A value of
+    /// `i16::MIN` indicates silence. Returns an error if the stream is not
+    /// configured or has no such crosspoint.
+    pub fn input_stream_mixer_gain(
+        &self,
+        stream: usize,
+        input_channel: u8,
+        output_channel: u8,
+    ) -> Result<i16> {
+        let mixer_unit = self.input(stream)?.mixer_unit()?;
+        self.mixer_unit_gain(mixer_unit, input_channel, output_channel)
+    }
+
+    /// Get the gain of a crosspoint of the output stream's Mixer Unit. See
+    /// `output_stream_mixer_gain`.
+    pub fn output_mixer_gain(&self, input_channel: u8, output_channel: u8) -> Result<i16> {
+        self.output_stream_mixer_gain(0, input_channel, output_channel)
+    }
+
+    /// Get the gain of a crosspoint of an output stream's Mixer Unit in units
+    /// of 1/256 dB. The input channels (1, 2, ...) are numbered consecutively
+    /// across the stream itself and the sources of the Mixer Unit; the output
+    /// channels (1, 2, ...) are the channels of the stream. A value of
+    /// `i16::MIN` indicates silence. Returns an error if the stream is not
+    /// configured or has no such crosspoint.
+    pub fn output_stream_mixer_gain(
+        &self,
+        stream: usize,
+        input_channel: u8,
+        output_channel: u8,
+    ) -> Result<i16> {
+        let mixer_unit = self.output(stream)?.mixer_unit()?;
+        self.mixer_unit_gain(mixer_unit, input_channel, output_channel)
+    }
+
+    /// Get the value of a control of a Processing Unit of the input stream.
+    /// See `input_stream_processing_control`.
+    pub fn input_processing_control(&self, unit: usize, control: ProcessingControl) -> Result<i32> {
+        self.input_stream_processing_control(0, unit, control)
+    }
+
+    /// Get the value of a control of a Processing Unit of an input stream as
+    /// set by the host. `unit` is the index of the Processing Unit in the
+    /// slice passed to `StreamConfig::with_processing_units`. Returns an error
+    /// if the stream is not configured or the Processing Unit has no such
+    /// control.
+    pub fn input_stream_processing_control(
+        &self,
+        stream: usize,
+        unit: usize,
+        control: ProcessingControl,
+    ) -> Result<i32> {
+        self.processing_unit_control(self.input(stream)?.processing_unit(unit)?, control)
+    }
+
+    /// Get the value of a control of a Processing Unit of the output stream.
+    /// See `output_stream_processing_control`.
+    pub fn output_processing_control(
+        &self,
+        unit: usize,
+        control: ProcessingControl,
+    ) -> Result<i32> {
+        self.output_stream_processing_control(0, unit, control)
+    }
+
+    /// Get the value of a control of a Processing Unit of an output stream as
+    /// set by the host. `unit` is the index of the Processing Unit in the
+    /// slice passed to `StreamConfig::with_processing_units`. Returns an error
+    /// if the stream is not configured or the Processing Unit has no such
+    /// control.
+    pub fn output_stream_processing_control(
+        &self,
+        stream: usize,
+        unit: usize,
+        control: ProcessingControl,
+    ) -> Result<i32> {
+        self.processing_unit_control(self.output(stream)?.processing_unit(unit)?, control)
+    }
+
+    /// Get the Input Pin selected by the Selector Unit of the input stream.
+    /// See `input_stream_selector`.
+    pub fn input_selector(&self) -> Result<u8> {
+        self.input_stream_selector(0)
+    }
+
+    /// Get the Input Pin selected by the Selector Unit of an input stream.
+    /// Input Pin 1 is the Input Terminal of the stream and the Input Pins 2,
+    /// 3, ... are the further Input Terminals passed to
+    /// `StreamConfig::with_selector`. Returns an error if the stream is not
+    /// configured or has no Selector Unit.
+    pub fn input_stream_selector(&self, stream: usize) -> Result<u8> {
+        self.selector_unit_input(self.input(stream)?.selector_unit()?)
+    }
+
+    /// Get the Input Pin selected by the Selector Unit of the output stream.
+    /// See `output_stream_selector`.
+    pub fn output_selector(&self) -> Result<u8> {
+        self.output_stream_selector(0)
     }
+
+    /// Get the Input Pin selected by the Selector Unit of an output stream.
+    /// Input Pin 1 is the Input Terminal of the stream and the Input Pins 2,
+    /// 3, ... are the further Input Terminals passed to
+    /// `StreamConfig::with_selector`. Returns an error if the stream is not
+    /// configured or has no Selector Unit.
+    pub fn output_stream_selector(&self, stream: usize) -> Result<u8> {
+        self.selector_unit_input(self.output(stream)?.selector_unit()?)
+    }
+
+    /// Check if the host has changed the Input Pin selected by the Selector
+    /// Unit of the input stream since the last call of this method. Returns
+    /// an error if the stream is not configured or has no Selector Unit.
+    pub fn input_selector_changed(&mut self) -> Result<bool> {
+        self.input_stream_selector_changed(0)
+    }
+
+    /// Check if the host has changed the Input Pin selected by the Selector
+    /// Unit of an input stream since the last call of this method. Returns an
+    /// error if the stream is not configured or has no Selector Unit.
+    pub fn input_stream_selector_changed(&mut self, stream: usize) -> Result<bool> {
+        let selector_unit = self.input(stream)?.selector_unit()?;
+        self.selector_unit_changed(selector_unit)
+    }
+
+    /// Check if the host has changed the Input Pin selected by the Selector
+    /// Unit of the output stream since the last call of this method. Returns
+    /// an error if the stream is not configured or has no Selector Unit.
+    pub fn output_selector_changed(&mut self) -> Result<bool> {
+        self.output_stream_selector_changed(0)
+    }
+
+    /// Check if the host has changed the Input Pin selected by the Selector
+    /// Unit of an output stream since the last call of this method. Returns an
+    /// error if the stream is not configured or has no Selector Unit.
+    pub fn output_stream_selector_changed(&mut self, stream: usize) -> Result<bool> {
+        let selector_unit = self.output(stream)?.selector_unit()?;
+        self.selector_unit_changed(selector_unit)
+    }
+
+    /// Get the ID of the Extension Unit of the input stream. See
+    /// `input_stream_extension_unit_id`.
+    pub fn input_extension_unit_id(&self) -> Result<u8> {
+        self.input_stream_extension_unit_id(0)
+    }
+
+    /// Get the ID of the Extension Unit of an input stream as passed to the
+    /// `AudioControlHandler`. Returns an error if the stream is not
+    /// configured or has no Extension Unit.
+    pub fn input_stream_extension_unit_id(&self, stream: usize) -> Result<u8> {
+        Ok(self.input(stream)?.extension_unit()?.id())
+    }
+
+    /// Get the ID of the Extension Unit of the output stream. See
+    /// `output_stream_extension_unit_id`.
+    pub fn output_extension_unit_id(&self) -> Result<u8> {
+        self.output_stream_extension_unit_id(0)
+    }
+
+    /// Get the ID of the Extension Unit of an output stream as passed to the
+    /// `AudioControlHandler`. Returns an error if the stream is not
+    /// configured or has no Extension Unit.
+    pub fn output_stream_extension_unit_id(&self, stream: usize) -> Result<u8> {
+        Ok(self.output(stream)?.extension_unit()?.id())
+    }
+
+    /// Get the state of the Mute Control of a channel of a Feature Unit.
+    /// Channel 0 is the master channel. Returns an error if `unit` is not a
+    /// Feature Unit or has no such channel.
+    pub fn feature_unit_mute(&self, unit: Entity, channel: u8) -> Result<bool> {
+        self.entities
+            .feature_unit(unit.id())
+            .and_then(|fu| fu.mute(channel))
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Get the value of the Volume Control of a channel of a Feature Unit in
+    /// units of 1/256 dB. Channel 0 is the master channel. Returns an error
+    /// if `unit` is not a Feature Unit or has no such channel.
+    pub fn feature_unit_volume(&self, unit: Entity, channel: u8) -> Result<i16> {
+        self.entities
+            .feature_unit(unit.id())
+            .and_then(|fu| fu.volume(channel))
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Change the state of the Mute Control of a channel of a Feature Unit,
+    /// e.g. when the user presses a mute button of the device. The host is
+    /// notified if the status endpoint is configured. Returns an error if
+    /// `unit` is not a Feature Unit or has no such control.
+    pub fn set_feature_unit_mute(&mut self, unit: Entity, channel: u8, mute: bool) -> Result<()> {
+        let fu = self
+            .entities
+            .feature_unit_mut(unit.id())
+            .ok_or(Error::InvalidValue)?;
+        if !fu.set_mute(channel, mute) {
+            return Err(Error::InvalidValue);
+        }
+        if let Some(ref mut status) = self.status {
+            status.notify(unit.id());
+        }
+        Ok(())
+    }
+
+    /// Change the value of the Volume Control of a channel of a Feature Unit
+    /// in units of 1/256 dB, e.g. when the user turns a volume knob of the
+    /// device. The value is clamped to the configured range. The host is
+    /// notified if the status endpoint is configured. Returns an error if
+    /// `unit` is not a Feature Unit or has no such control.
+    pub fn set_feature_unit_volume(
+        &mut self,
+        unit: Entity,
+        channel: u8,
+        volume: i16,
+    ) -> Result<()> {
+        let fu = self
+            .entities
+            .feature_unit_mut(unit.id())
+            .ok_or(Error::InvalidValue)?;
+        if !fu.set_volume(channel, volume) {
+            return Err(Error::InvalidValue);
+        }
+        if let Some(ref mut status) = self.status {
+            status.notify(unit.id());
+        }
+        Ok(())
+    }
+
+    /// Notify the host by means of the status endpoint that a control of the
+    /// Terminal or Unit `entity_id` has been changed by the device. The host
+    /// then re-reads the controls with GET_CUR requests. The interrupt stays
+    /// pending until the host sends a GET_STAT request or a successful
+    /// GET_CUR request to the Terminal or Unit. GET_STAT requests are answered
+    /// by the `AudioClass` and not passed to the `AudioControlHandler`.
+    /// Returns an error if the status endpoint is not configured or the
+    /// Terminal or Unit does not exist.
+    pub fn notify_control_changed(&mut self, entity_id: u8) -> Result<()> {
+        let status = self.status.as_mut().ok_or(Error::InvalidValue)?;
+        if !self.entities.contains(entity_id) {
+            return Err(Error::InvalidValue);
+        }
+        status.notify(entity_id);
+        Ok(())
+    }
+
+    /// Get the gain of a crosspoint of a Mixer Unit in units of 1/256 dB. The
+    /// input channels (1, 2, ...) are numbered consecutively across the Input
+    /// Pins. A value of `i16::MIN` indicates silence. Returns an error if
+    /// `unit` is not a Mixer Unit or has no such crosspoint.
+    pub fn mixer_unit_gain(
+        &self,
+        unit: Entity,
+        input_channel: u8,
+        output_channel: u8,
+    ) -> Result<i16> {
+        self.entities
+            .mixer_unit(unit.id())
+            .and_then(|mu| mu.gain(input_channel, output_channel))
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Get the value of a control of a Processing Unit as set by the host.
+    /// Returns an error if `unit` is not a Processing Unit or has no such
+    /// control.
+    pub fn processing_unit_control(&self, unit: Entity, control: ProcessingControl) -> Result<i32> {
+        self.entities
+            .processing_unit(unit.id())
+            .and_then(|pu| pu.value(control))
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Get the Input Pin (1, 2, ...) selected by a Selector Unit. Returns an
+    /// error if `unit` is not a Selector Unit.
+    pub fn selector_unit_input(&self, unit: Entity) -> Result<u8> {
+        self.entities
+            .selector_unit(unit.id())
+            .map(|su| su.current())
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Check if the host has changed the Input Pin selected by a Selector
+    /// Unit since the last call of this method. Returns an error if `unit` is
+    /// not a Selector Unit.
+    pub fn selector_unit_changed(&mut self, unit: Entity) -> Result<bool> {
+        self.entities
+            .selector_unit_mut(unit.id())
+            .map(|su| su.take_changed())
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Get a reference to the handler of the class-specific requests
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Get a mutable reference to the handler of the class-specific requests
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// Get the sampling rate of the input stream as selected by the host.
+    /// Returns an error if the stream is not configured.
+    pub fn input_sample_rate(&self) -> Result<u32> {
+        self.input_stream_sample_rate(0)
+    }
+
+    /// Get the sampling rate of an input stream as selected by the host.
+    /// Returns an error if the stream is not configured.
+    pub fn input_stream_sample_rate(&self, stream: usize) -> Result<u32> {
+        self.input(stream).map(|si| si.sample_rate)
+    }
+
+    /// Get the sampling rate of the output stream as selected by the host.
+    /// Returns an error if the stream is not configured.
+    pub fn output_sample_rate(&self) -> Result<u32> {
+        self.output_stream_sample_rate(0)
+    }
+
+    /// Get the sampling rate of an output stream as selected by the host.
+    /// Returns an error if the stream is not configured.
+    pub fn output_stream_sample_rate(&self, stream: usize) -> Result<u32> {
+        self.output(stream).map(|si| si.sample_rate)
+    }
+
+    /// Check if the host has changed the sampling rate of the input stream
+    /// since the last call of this method. Returns an error if the stream is
+    /// not configured.
+    pub fn input_sample_rate_changed(&mut self) -> Result<bool> {
+        self.input_stream_sample_rate_changed(0)
+    }
+
+    /// Check if the host has changed the sampling rate of an input stream
+    /// since the last call of this method. Returns an error if the stream is
+    /// not configured.
+    pub fn input_stream_sample_rate_changed(&mut self, stream: usize) -> Result<bool> {
+        self.input_mut(stream)
+            .map(|si| core::mem::take(&mut si.sample_rate_changed))
+    }
+
+    /// Check if the host has changed the sampling rate of the output stream
+    /// since the last call of this method. Returns an error if the stream is
+    /// not configured.
+    pub fn output_sample_rate_changed(&mut self) -> Result<bool> {
+        self.output_stream_sample_rate_changed(0)
+    }
+
+    /// Check if the host has changed the sampling rate of an output stream
+    /// since the last call of this method. Returns an error if the stream is
+    /// not configured.
+    pub fn output_stream_sample_rate_changed(&mut self, stream: usize) -> Result<bool> {
+        self.output_mut(stream)
+            .map(|si| core::mem::take(&mut si.sample_rate_changed))
+    }
+
+    /// Set the feedback value of the asynchronous output stream. See
+    /// `set_output_stream_feedback`.
+    pub fn set_output_feedback(&mut self, value: u32) -> Result<()> {
+        self.set_output_stream_feedback(0, value)
+    }
+
+    /// Set the feedback value of an asynchronous output stream, i.e. the number
+    /// of samples per frame (1 ms) in 10.14 format as measured by the device.
+    /// The value is sent to the host whenever the host polls the feedback
+    /// endpoint. When the host changes the sampling rate, the feedback value
+    /// is reset to the nominal value. Returns an error if the output stream is
+    /// not configured or has no feedback endpoint.
+    pub fn set_output_stream_feedback(&mut self, stream: usize, value: u32) -> Result<()> {
+        let info = self.output_mut(stream)?;
+        let fb = info.feedback.as_mut().ok_or(Error::InvalidValue)?;
+        fb.value = value;
+        if info.alt_setting != DEFAULT_ALTERNATE_SETTING {
+            fb.send();
+        }
+        Ok(())
+    }
+
+    /// Get the recipient of a class-specific request addressed to the
+    /// AudioControl interface or to the isochronous endpoint of a stream
+    fn control_target(&self, req: &control::Request) -> Option<ControlTarget> {
+        if req.request_type != RequestType::Class {
+            return None;
+        }
+        match req.recipient {
+            Recipient::Interface if req.index as u8 == self.control_iface.into() => {
+                Some(ControlTarget::Entity((req.index >> 8) as u8))
+            }
+            Recipient::Endpoint => {
+                let address = EndpointAddress::from(req.index as u8);
+                let inputs = self.inputs.iter().flatten().map(|si| si.endpoint.address());
+                let outputs = self
+                    .outputs
+                    .iter()
+                    .flatten()
+                    .map(|si| si.endpoint.address());
+                let mut addresses = inputs.chain(outputs);
+                addresses
+                    .any(|a| a == address)
+                    .then_some(ControlTarget::Endpoint(address))
+            }
+            _ => None,
+        }
+    }
+
+    /// Check if a class-specific request is addressed to an AudioStreaming
+    /// interface or a feedback endpoint. Such requests are not supported.
+    fn is_streaming_request(&self, req: &control::Request) -> bool {
+        if req.request_type != RequestType::Class {
+            return false;
+        }
+        let inputs = self.inputs.iter().flatten().map(|si| si.interface);
+        let outputs = self.outputs.iter().flatten().map(|si| si.interface);
+        let mut feedback = self
+            .outputs
+            .iter()
+            .flatten()
+            .filter_map(|si| si.feedback.as_ref());
+        match req.recipient {
+            Recipient::Interface => inputs.chain(outputs).any(|i| req.index as u8 == i.into()),
+            Recipient::Endpoint => {
+                feedback.any(|fb| req.index as u8 == fb.endpoint.address().into())
+            }
+            _ => false,
+        }
+    }
+
+    /// Handle a class-specific GET request. Returns the number of bytes
+    /// written to `data` or `None` to reject the request.
+    fn get_control(
+        &mut self,
+        req: &control::Request,
+        target: ControlTarget,
+        data: &mut [u8],
+    ) -> Option<usize> {
+        let request = ControlRequest::new(req, target)?;
+        match target {
+            ControlTarget::Entity(id) => {
+                if !self.entities.contains(id) {
+                    return None;
+                }
+                if let Some(ref mut status) = self.status {
+                    if request.attribute == Attribute::Stat {
+                        data[..2].copy_from_slice(&[status.acknowledge(id), id]);
+                        return Some(2);
+                    }
+                }
+                let len = if let Some(xu) = self.entities.extension_unit(id) {
+                    if !xu.accepts(&request) {
+                        return None;
+                    }
+                    self.handler.get(&request, data)
+                } else {
+                    self.entities
+                        .get_control(id, req.request, req.value, data)
+                        .or_else(|| self.handler.get(&request, data))
+                };
+                // reading a control of the originator clears its interrupt
+                if let (Some(_), Some(status)) = (len, self.status.as_mut()) {
+                    if request.attribute == Attribute::Cur {
+                        status.acknowledge(id);
+                    }
+                }
+                return len;
+            }
+            ControlTarget::Endpoint(address) => {
+                if is_sampling_freq_request(req) {
+                    if let Some(si) = self
+                        .inputs
+                        .iter()
+                        .flatten()
+                        .find(|si| si.endpoint.address() == address)
+                    {
+                        return si.get_endpoint_control(req, data);
+                    }
+                    return self
+                        .outputs
+                        .iter()
+                        .flatten()
+                        .find(|si| si.endpoint.address() == address)
+                        .and_then(|si| si.get_endpoint_control(req, data));
+                }
+            }
+        }
+        self.handler.get(&request, data)
+    }
+
+    /// Handle a class-specific SET request. Returns `false` to reject the
+    /// request.
+    fn set_control(&mut self, req: &control::Request, target: ControlTarget, data: &[u8]) -> bool {
+        let Some(request) = ControlRequest::new(req, target) else {
+            return false;
+        };
+        match target {
+            ControlTarget::Entity(id) => {
+                if !self.entities.contains(id) {
+                    return false;
+                }
+                if let Some(xu) = self.entities.extension_unit(id) {
+                    if !xu.accepts(&request) {
+                        return false;
+                    }
+                } else if self.entities.set_control(id, req.request, req.value, data) {
+                    return true;
+                }
+            }
+            ControlTarget::Endpoint(address) => {
+                if is_sampling_freq_request(req) {
+                    if let Some(si) = self
+                        .inputs
+                        .iter_mut()
+                        .flatten()
+                        .find(|si| si.endpoint.address() == address)
+                    {
+                        return si.set_endpoint_control(req, data);
+                    }
+                    return self
+                        .outputs
+                        .iter_mut()
+                        .flatten()
+                        .find(|si| si.endpoint.address() == address)
+                        .is_some_and(|si| si.set_endpoint_control(req, data));
+                }
+            }
+        }
+        self.handler.set(&request, data)
+    }
+}
+
+/// Check if a request addresses the current setting of the Sampling Frequency
+/// Control of an endpoint
+fn is_sampling_freq_request(req: &control::Request) -> bool {
+    (req.request == SET_CUR || req.request == GET_CUR) && req.value >> 8 == SAMPLING_FREQ_CONTROL
 }
 
-impl<B: UsbBus> UsbClass<B> for AudioClass<'_, B> {
+impl<B: UsbBus, H: AudioControlHandler, const U: usize> UsbClass<B> for AudioClass<'_, B, H, U> {
     fn get_configuration_descriptors(
         &self,
         writer: &mut DescriptorWriter,
     ) -> usb_device::Result<()> {
-        let mut in_collection = 0u8;
-        if self.input.is_some() {
-            in_collection += 1;
-        }
-        if self.output.is_some() {
-            in_collection += 1;
-        }
+        let inputs = || self.inputs.iter().flatten();
+        let outputs = || self.outputs.iter().flatten();
+        let in_collection = (inputs().count() + outputs().count()) as u8;
 
+        let function_name = self.function_name.map(|(index, _)| index);
         writer.iad(
             self.control_iface,
             in_collection + 1, // Number of interfaces: control + streaming
             AUDIO,             // bFunctionClass
             AUDIOCONTROL,
-            0x00, // bFunctionProtocol
-            None, // iFunction
+            0x00,          // bFunctionProtocol
+            function_name, // iFunction
         )?;
 
         // write Class-specific Audio Control (AC) Interface Descriptors
         writer.interface(self.control_iface, AUDIO, AUDIOCONTROL, 0x00)?;
 
-        let total_length = 8u16 + (1 + 21) * in_collection as u16;
+        let total_length = 8 + in_collection as u16 + self.entities.descriptors_length();
 
-        let mut ac_header = [
+        let mut ac_header = [0u8; 6 + 2 * MAX_STREAMS];
+        ac_header[..6].copy_from_slice(&[
             HEADER, // bDescriptorSubtype
             0x00,
             0x01, // bcdADC
             total_length as u8,
             (total_length >> 8) as u8, // wTotalLength
             in_collection,             // number of AS interfaces
-            0x00,
-            0x00, // placeholders for baInterfaceNr
-        ];
+        ]);
         let mut ndx = 6;
-        if let Some(ref input) = self.input {
-            ac_header[ndx] = input.interface.into();
-            ndx += 1;
-        }
-        if let Some(ref output) = self.output {
-            ac_header[ndx] = output.interface.into();
+        for iface in inputs()
+            .map(|a| a.interface)
+            .chain(outputs().map(|a| a.interface))
+        {
+            ac_header[ndx] = iface.into(); // baInterfaceNr
             ndx += 1;
         }
         writer.write(CS_INTERFACE, &ac_header[..ndx])?;
-        if let Some(ref a) = self.input {
-            a.write_ac_descriptors(writer)?;
-        }
-        if let Some(ref a) = self.output {
-            a.write_ac_descriptors(writer)?;
+        self.entities.write_descriptors(writer)?;
+
+        // Standard AC Interrupt Endpoint Descriptor
+        if let Some(ref status) = self.status {
+            writer.endpoint_ex(&status.endpoint, |buf| {
+                let extra = buf.get_mut(..2).ok_or(UsbError::BufferOverflow)?;
+                extra.copy_from_slice(&[
+                    0x00, // bRefresh
+                    0x00, // bSynchAddress
+                ]);
+                Ok(2)
+            })?;
         }
 
         // write Audio Streaming (AS) and endpoint (EP) descriptors
-        if let Some(ref a) = self.input {
+        for a in inputs() {
             a.write_as_and_ep_descriptors(writer)?;
         }
-        if let Some(ref a) = self.output {
+        for a in outputs() {
             a.write_as_and_ep_descriptors(writer)?;
         }
         Ok(())
     }
 
     fn control_in(&mut self, xfer: ControlIn<B>) {
-        let req = xfer.request();
+        let req = *xfer.request();
+        if let Some(target) = self.control_target(&req) {
+            let mut data = [0u8; MAX_CONTROL_SIZE];
+            match self.get_control(&req, target, &mut data) {
+                Some(len) => xfer.accept_with(&data[..len.min(data.len())]).ok(),
+                None => xfer.reject().ok(),
+            };
+            return;
+        }
+        if self.is_streaming_request(&req) {
+            xfer.reject().ok();
+            return;
+        }
         if req.request_type == RequestType::Standard
             && req.recipient == Recipient::Interface
             && req.request == Request::GET_INTERFACE
             && req.length == 1
         {
             let iface = req.index as u8;
-            if let Some(info) = self.input.as_ref() {
-                if iface == info.interface.into() {
-                    xfer.accept_with(&[info.alt_setting]).ok();
-                    return;
-                }
-            }
-            if let Some(info) = self.output.as_ref() {
-                if iface == info.interface.into() {
-                    xfer.accept_with(&[info.alt_setting]).ok();
-                }
+            let alt_setting = if let Some(info) = self
+                .inputs
+                .iter()
+                .flatten()
+                .find(|si| iface == si.interface.into())
+            {
+                Some(info.alt_setting)
+            } else {
+                self.outputs
+                    .iter()
+                    .flatten()
+                    .find(|si| iface == si.interface.into())
+                    .map(|info| info.alt_setting)
+            };
+            if let Some(alt_setting) = alt_setting {
+                xfer.accept_with(&[alt_setting]).ok();
             }
         }
     }
 
     fn control_out(&mut self, xfer: ControlOut<B>) {
-        let req = xfer.request();
+        let req = *xfer.request();
+        if let Some(target) = self.control_target(&req) {
+            if self.set_control(&req, target, xfer.data()) {
+                xfer.accept().ok();
+            } else {
+                xfer.reject().ok();
+            }
+            return;
+        }
+        if self.is_streaming_request(&req) {
+            xfer.reject().ok();
+            return;
+        }
         if req.request_type == RequestType::Standard
             && req.recipient == Recipient::Interface
             && req.request == Request::SET_INTERFACE
@@ -559,17 +2527,87 @@ impl<B: UsbBus> UsbClass<B> for AudioClass<'_, B> {
             let iface = req.index as u8;
             let alt_setting = req.value;
 
-            if let Some(info) = self.input.as_mut() {
-                if iface == info.interface.into() {
-                    info.alt_setting = alt_setting as u8;
+            if let Some(info) = self
+                .inputs
+                .iter_mut()
+                .flatten()
+                .find(|si| iface == si.interface.into())
+            {
+                if info.set_alt_setting(alt_setting) {
                     xfer.accept().ok();
+                } else {
+                    xfer.reject().ok();
+                }
+                return;
+            }
+            if let Some(info) = self
+                .outputs
+                .iter_mut()
+                .flatten()
+                .find(|si| iface == si.interface.into())
+            {
+                if !info.set_alt_setting(alt_setting) {
+                    xfer.reject().ok();
                     return;
                 }
+                xfer.accept().ok();
+                if let Some(ref fb) = info.feedback {
+                    if info.alt_setting != DEFAULT_ALTERNATE_SETTING {
+                        fb.send();
+                    }
+                }
             }
-            if let Some(info) = self.output.as_mut() {
-                if iface == info.interface.into() {
-                    info.alt_setting = alt_setting as u8;
-                    xfer.accept().ok();
+        }
+    }
+
+    fn get_string(&self, index: StringIndex, _lang_id: LangID) -> Option<&str> {
+        if let Some((function_index, name)) = self.function_name {
+            if function_index == index {
+                return Some(name);
+            }
+        }
+        let index = u8::from(index);
+        if let Some(id) = self.entities.named(index) {
+            return match self.topology {
+                Some(topology) => topology.name(id),
+                None => {
+                    let inputs = self.inputs.iter().flatten().map(|si| si.entity_name(id));
+                    let outputs = self.outputs.iter().flatten().map(|si| si.entity_name(id));
+                    inputs.chain(outputs).flatten().next()
+                }
+            };
+        }
+        let inputs = self
+            .inputs
+            .iter()
+            .flatten()
+            .map(|si| si.channel_name(index));
+        let outputs = self
+            .outputs
+            .iter()
+            .flatten()
+            .map(|si| si.channel_name(index));
+        inputs.chain(outputs).flatten().next()
+    }
+
+    fn reset(&mut self) {
+        if let Some(ref mut status) = self.status {
+            status.reset();
+        }
+    }
+
+    fn endpoint_in_complete(&mut self, addr: EndpointAddress) {
+        if let Some(ref mut status) = self.status {
+            if addr == status.endpoint.address() {
+                status.busy = false;
+                status.send();
+                return;
+            }
+        }
+        for info in self.outputs.iter().flatten() {
+            if let Some(ref fb) = info.feedback {
+                if addr == fb.endpoint.address() && info.alt_setting != DEFAULT_ALTERNATE_SETTING {
+                    fb.send();
                 }
             }
         }
@@ -0,0 +1,253 @@
+//! Mixer Unit with host controlled mixing gains
+//!
+
+use crate::class_codes::*;
+use crate::cluster::Cluster;
+use crate::topology::AcDescriptor;
+
+/// Maximum number of Input Pins of a Mixer Unit
+pub(crate) const MAX_INPUT_PINS: usize = 4;
+/// Maximum total number of input channels of a Mixer Unit
+pub(crate) const MAX_INPUT_CHANNELS: usize = 16;
+/// Maximum number of output channels of a Mixer Unit
+pub(crate) const MAX_OUTPUT_CHANNELS: usize = 8;
+
+/// Maximum size of the bmControls field
+const MAX_CONTROLS_SIZE: usize = MAX_INPUT_CHANNELS * MAX_OUTPUT_CHANNELS / 8;
+
+/// Gain value indicating silence (-infinity dB)
+const GAIN_SILENCE: i16 = i16::MIN;
+
+/// Signal of another stream mixed in by a Mixer Unit
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MixerSource {
+    /// Signal fed to the USB streaming Output Terminal of an input stream,
+    /// e.g. a microphone signal, identified by the stream number
+    InputStream(usize),
+    /// Signal fed to the Output Terminal of an output stream, e.g. the
+    /// playback signal, identified by the stream number
+    OutputStream(usize),
+}
+
+/// Configuration of a Mixer Unit placed in front of the Output Terminal of a
+/// stream or added to a `Topology`.
+///
+/// The Mixer Unit of a stream mixes the signal of the stream itself (Input
+/// Pin 1) with the signals of other streams (Input Pins 2, 3, ...). The output channels of the
+/// Mixer Unit correspond to the channels of the stream. Each pair of an input
+/// channel and an output channel (crosspoint) has a gain in units of 1/256 dB.
+/// Initially, the channels of the stream itself are passed to the
+/// corresponding output channels at 0 dB and all other crosspoints are
+/// silent.
+#[derive(Clone, Copy, Debug)]
+pub struct MixerConfig<'a> {
+    sources: &'a [MixerSource],
+    crosspoints: Option<&'a [(u8, u8)]>,
+    gain_min: i16,
+    gain_max: i16,
+    gain_res: i16,
+}
+
+impl<'a> MixerConfig<'a> {
+    /// Create a Mixer Unit configuration mixing the signals of the streams
+    /// `sources` into the stream. Up to 3 sources are supported. The sources
+    /// must not lead to a cycle, e.g. two streams mixing in each other. All
+    /// crosspoints are programmable by the host and the gain range defaults
+    /// to -60 dB to 0 dB in steps of 1 dB. A configuration added to a
+    /// `Topology` has no sources.
+    pub fn new(sources: &'a [MixerSource]) -> MixerConfig<'a> {
+        MixerConfig {
+            sources,
+            crosspoints: None,
+            gain_min: -60 * 256,
+            gain_max: 0,
+            gain_res: 256,
+        }
+    }
+
+    /// Restrict the crosspoints programmable by the host to the pairs of
+    /// input channel number and output channel number (1, 2, ...) in
+    /// `crosspoints`. The input channels are numbered consecutively across
+    /// all Input Pins.
+    pub fn crosspoints(self, crosspoints: &'a [(u8, u8)]) -> MixerConfig<'a> {
+        MixerConfig {
+            crosspoints: Some(crosspoints),
+            ..self
+        }
+    }
+
+    /// Set the range of the gains in units of 1/256 dB. `min` must be less
+    /// than `max` and `res` must be positive.
+    pub fn gain_range(self, min: i16, max: i16, res: i16) -> MixerConfig<'a> {
+        MixerConfig {
+            gain_min: min,
+            gain_max: max,
+            gain_res: res,
+            ..self
+        }
+    }
+
+    /// Streams mixed into the stream
+    pub(crate) fn sources(&self) -> &'a [MixerSource] {
+        self.sources
+    }
+}
+
+/// Mixer Unit state including the current gains of the crosspoints
+pub(crate) struct MixerUnit {
+    pub(crate) id: u8,
+    num_pins: u8,
+    source_ids: [u8; MAX_INPUT_PINS],
+    input_channels: u8,
+    output_channels: u8,
+    cluster: Cluster,
+    controls: [u8; MAX_CONTROLS_SIZE],
+    gain_min: i16,
+    gain_max: i16,
+    gain_res: i16,
+    gain: [[i16; MAX_OUTPUT_CHANNELS]; MAX_INPUT_CHANNELS],
+}
+
+impl MixerUnit {
+    /// Create a Mixer Unit state. `sources` are the pairs of ID and number of
+    /// channels of the Terminals or Units connected to the Input Pins and
+    /// `cluster` is the output cluster. Returns `None` if the configuration is
+    /// not valid.
+    pub(crate) fn new(
+        config: &MixerConfig,
+        id: u8,
+        sources: &[(u8, u8)],
+        cluster: Cluster,
+    ) -> Option<MixerUnit> {
+        let output_channels = cluster.channels;
+        let input_channels = sources.iter().map(|&(_, ch)| ch as usize).sum::<usize>();
+        if sources.is_empty()
+            || sources.len() > MAX_INPUT_PINS
+            || input_channels == 0
+            || input_channels > MAX_INPUT_CHANNELS
+            || output_channels == 0
+            || output_channels as usize > MAX_OUTPUT_CHANNELS
+            || config.gain_min >= config.gain_max
+            || config.gain_res <= 0
+        {
+            return None;
+        }
+        let mut mu = MixerUnit {
+            id,
+            num_pins: sources.len() as u8,
+            source_ids: [0; MAX_INPUT_PINS],
+            input_channels: input_channels as u8,
+            output_channels,
+            cluster,
+            controls: [0; MAX_CONTROLS_SIZE],
+            gain_min: config.gain_min,
+            gain_max: config.gain_max,
+            gain_res: config.gain_res,
+            gain: [[GAIN_SILENCE; MAX_OUTPUT_CHANNELS]; MAX_INPUT_CHANNELS],
+        };
+        for (source_id, &(id, _)) in mu.source_ids.iter_mut().zip(sources) {
+            *source_id = id;
+        }
+        let unity = 0.clamp(config.gain_min, config.gain_max);
+        for ch in 0..sources[0].1.min(output_channels) {
+            mu.gain[ch as usize][ch as usize] = unity;
+        }
+        match config.crosspoints {
+            Some(crosspoints) => {
+                for &(icn, ocn) in crosspoints {
+                    let bit = mu.control_bit(icn, ocn)?;
+                    mu.controls[bit / 8] |= 0x80 >> (bit % 8);
+                }
+            }
+            None => {
+                for bit in 0..input_channels * output_channels as usize {
+                    mu.controls[bit / 8] |= 0x80 >> (bit % 8);
+                }
+            }
+        }
+        Some(mu)
+    }
+
+    /// Position of a crosspoint in the bmControls bitmap
+    fn control_bit(&self, icn: u8, ocn: u8) -> Option<usize> {
+        if (1..=self.input_channels).contains(&icn) && (1..=self.output_channels).contains(&ocn) {
+            Some((icn as usize - 1) * self.output_channels as usize + ocn as usize - 1)
+        } else {
+            None
+        }
+    }
+
+    /// Check if a crosspoint exists and is programmable
+    fn is_programmable(&self, icn: u8, ocn: u8) -> bool {
+        self.control_bit(icn, ocn)
+            .is_some_and(|bit| self.controls[bit / 8] & (0x80 >> (bit % 8)) != 0)
+    }
+
+    /// Size of the bmControls field
+    fn controls_size(&self) -> usize {
+        (self.input_channels as usize * self.output_channels as usize).div_ceil(8)
+    }
+
+    /// Current gain of a crosspoint
+    pub(crate) fn gain(&self, icn: u8, ocn: u8) -> Option<i16> {
+        self.control_bit(icn, ocn)
+            .map(|_| self.gain[icn as usize - 1][ocn as usize - 1])
+    }
+
+    /// Handle a GET request. Returns the number of bytes written to `data` or
+    /// `None` if the request is not supported.
+    pub(crate) fn get(&self, request: u8, value: u16, data: &mut [u8; 2]) -> Option<usize> {
+        let icn = (value >> 8) as u8;
+        let ocn = value as u8;
+        if !self.is_programmable(icn, ocn) {
+            return None;
+        }
+        let gain = match request {
+            GET_CUR => self.gain[icn as usize - 1][ocn as usize - 1],
+            GET_MIN => self.gain_min,
+            GET_MAX => self.gain_max,
+            GET_RES => self.gain_res,
+            _ => return None,
+        };
+        *data = gain.to_le_bytes();
+        Some(2)
+    }
+
+    /// Handle a SET request. Returns `false` if the request is not supported.
+    pub(crate) fn set(&mut self, request: u8, value: u16, data: &[u8]) -> bool {
+        let icn = (value >> 8) as u8;
+        let ocn = value as u8;
+        if request != SET_CUR || !self.is_programmable(icn, ocn) || data.len() < 2 {
+            return false;
+        }
+        let gain = i16::from_le_bytes([data[0], data[1]]);
+        self.gain[icn as usize - 1][ocn as usize - 1] = if gain == GAIN_SILENCE {
+            gain
+        } else {
+            gain.clamp(self.gain_min, self.gain_max)
+        };
+        true
+    }
+}
+
+impl AcDescriptor for MixerUnit {
+    fn id(&self) -> u8 {
+        self.id
+    }
+
+    fn write_descriptor(&self, desc: &mut [u8], name: u8) -> usize {
+        let num_pins = self.num_pins as usize;
+        let controls_size = self.controls_size();
+        desc[0] = MIXER_UNIT; // bDescriptorSubtype
+        desc[1] = self.id; // bUnitID
+        desc[2] = self.num_pins; // bNrInPins
+        desc[3..3 + num_pins].copy_from_slice(&self.source_ids[..num_pins]); // baSourceID
+        let mut ndx = 3 + num_pins;
+        desc[ndx..ndx + 4].copy_from_slice(&self.cluster.descriptor()); // bNrChannels, wChannelConfig, iChannelNames
+        ndx += 4;
+        desc[ndx..ndx + controls_size].copy_from_slice(&self.controls[..controls_size]); // bmControls
+        ndx += controls_size;
+        desc[ndx] = name; // iMixer
+        ndx + 1
+    }
+}
@@ -0,0 +1,117 @@
+//! Distribution of audio frames to the packets of an isochronous stream
+//!
+
+/// Number of USB frames per second (full-speed)
+const FRAMES_PER_SECOND: u32 = 1000;
+
+/// Scheduler determining the number of audio frames (one sample per channel)
+/// to be sent in each packet of an input stream.
+///
+/// At sampling rates that are not a multiple of 1 kHz, the number of audio
+/// frames per USB frame is fractional. The scheduler spreads the fractional
+/// part across the packets such that the average data rate matches the
+/// sampling rate, e.g. at 44.1 kHz nine packets of 44 audio frames are
+/// followed by one packet of 45 audio frames.
+///
+/// `next_frames` is to be called once per USB frame (1 ms) when the packet
+/// is written. The sampling rate should be updated when the host changes it,
+/// e.g. `scheduler.set_sample_rate(usb_audio.input_sample_rate()?)`.
+#[derive(Clone, Copy, Debug)]
+pub struct PacketScheduler {
+    sample_rate: u32,
+    /// Accumulated fractional audio frames in units of 1/1000 frame
+    remainder: u32,
+}
+
+impl PacketScheduler {
+    /// Create a scheduler for a sampling rate in samples/second
+    pub fn new(sample_rate: u32) -> PacketScheduler {
+        PacketScheduler {
+            sample_rate,
+            remainder: 0,
+        }
+    }
+
+    /// Sampling rate in samples/second
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Set the sampling rate in samples/second. The packet pattern restarts
+    /// if the sampling rate changes.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            *self = PacketScheduler::new(sample_rate);
+        }
+    }
+
+    /// Restart the packet pattern, e.g. when the host selects an alternate
+    /// setting of the stream
+    pub fn reset(&mut self) {
+        self.remainder = 0;
+    }
+
+    /// Maximum number of audio frames of a packet
+    pub fn max_frames(&self) -> usize {
+        self.sample_rate.div_ceil(FRAMES_PER_SECOND) as usize
+    }
+
+    /// Number of audio frames to be sent in the next packet
+    pub fn next_frames(&mut self) -> usize {
+        let mut frames = self.sample_rate / FRAMES_PER_SECOND;
+        self.remainder += self.sample_rate % FRAMES_PER_SECOND;
+        if self.remainder >= FRAMES_PER_SECOND {
+            self.remainder -= FRAMES_PER_SECOND;
+            frames += 1;
+        }
+        frames as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractional_rate() {
+        let mut scheduler = PacketScheduler::new(44100);
+        assert_eq!(scheduler.max_frames(), 45);
+        for _ in 0..3 {
+            for _ in 0..9 {
+                assert_eq!(scheduler.next_frames(), 44);
+            }
+            assert_eq!(scheduler.next_frames(), 45);
+        }
+    }
+
+    #[test]
+    fn integer_rate() {
+        let mut scheduler = PacketScheduler::new(48000);
+        assert_eq!(scheduler.max_frames(), 48);
+        let total: usize = (0..1000).map(|_| scheduler.next_frames()).sum();
+        assert_eq!(total, 48000);
+        assert_eq!(scheduler.next_frames(), 48);
+    }
+
+    #[test]
+    fn set_sample_rate() {
+        let mut scheduler = PacketScheduler::new(48000);
+        scheduler.next_frames();
+        scheduler.set_sample_rate(44100);
+        assert_eq!(scheduler.sample_rate(), 44100);
+        let total: usize = (0..1000).map(|_| scheduler.next_frames()).sum();
+        assert_eq!(total, 44100);
+        // the pattern restarts after a change only
+        for _ in 0..5 {
+            scheduler.next_frames();
+        }
+        scheduler.set_sample_rate(44100);
+        for _ in 0..4 {
+            assert_eq!(scheduler.next_frames(), 44);
+        }
+        assert_eq!(scheduler.next_frames(), 45);
+        scheduler.set_sample_rate(48000);
+        assert_eq!(scheduler.max_frames(), 48);
+        assert_eq!(scheduler.next_frames(), 48);
+    }
+}
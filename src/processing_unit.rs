@@ -0,0 +1,441 @@
+//! Processing Units with host controlled processing parameters
+//!
+
+use crate::class_codes::*;
+use crate::cluster::Cluster;
+use crate::topology::AcDescriptor;
+
+/// Maximum number of modes of an Up/Down-mix or Dolby Prologic Processing Unit
+pub(crate) const MAX_MODES: usize = 8;
+
+/// Maximum number of Processing Units of a stream
+pub(crate) const MAX_PROCESSING_UNITS: usize = 4;
+
+/// Maximum number of controls of a Processing Unit
+const MAX_CONTROLS: usize = 6;
+
+/// Control of a Processing Unit
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProcessingControl {
+    /// Processing enabled (1) or bypassed (0)
+    Enable,
+    /// Selected mode (1, 2, ...) of an Up/Down-mix or Dolby Prologic
+    /// Processing Unit
+    ModeSelect,
+    /// Spaciousness of a 3D Stereo Extender in percent (0 to 255)
+    Spaciousness,
+    /// Reverberation level in percent (0 to 100)
+    ReverbLevel,
+    /// Reverberation time in units of 1/256 s
+    ReverbTime,
+    /// Reverberation delay feedback in percent (0 to 255)
+    ReverbFeedback,
+    /// Chorus level in percent (0 to 100)
+    ChorusLevel,
+    /// Chorus modulation rate in units of 1/256 Hz
+    ChorusRate,
+    /// Chorus modulation depth in units of 1/256 ms
+    ChorusDepth,
+    /// Compression ratio of a Dynamic Range Compressor in units of 1/256
+    CompressionRatio,
+    /// Maximum output amplitude of a Dynamic Range Compressor in units of
+    /// 1/256 dB
+    MaxAmplitude,
+    /// Threshold of a Dynamic Range Compressor in units of 1/256 dB
+    Threshold,
+    /// Attack time of a Dynamic Range Compressor in units of 1/256 ms
+    AttackTime,
+    /// Release time of a Dynamic Range Compressor in units of 1/256 ms
+    ReleaseTime,
+}
+
+/// Properties of a control of a Processing Unit
+struct ControlInfo {
+    control: ProcessingControl,
+    selector: u8,
+    /// Bit of the control in bmControls, which does not follow from the
+    /// Control Selector for all Processing Units
+    bit: u8,
+    /// Size of the parameter block in bytes
+    size: u8,
+    min: i32,
+    max: i32,
+    default: i32,
+}
+
+const fn control(
+    control: ProcessingControl,
+    selector: u8,
+    bit: u8,
+    size: u8,
+    min: i32,
+    max: i32,
+    default: i32,
+) -> ControlInfo {
+    ControlInfo {
+        control,
+        selector,
+        bit,
+        size,
+        min,
+        max,
+        default,
+    }
+}
+
+use ProcessingControl::*;
+
+const ENABLE: ControlInfo = control(Enable, 0x01, 0, 1, 0, 1, 0);
+
+const MODE_CONTROLS: [ControlInfo; 2] = [ENABLE, control(ModeSelect, 0x02, 1, 1, 1, 1, 1)];
+
+const DDD_CONTROLS: [ControlInfo; 2] = [
+    ENABLE,
+    control(Spaciousness, DDD_SPACIOUSNESS_CONTROL, 1, 1, 0, 255, 0),
+];
+
+const REVERB_CONTROLS: [ControlInfo; 4] = [
+    ENABLE,
+    control(ReverbLevel, REVERB_LEVEL_CONTROL, 2, 1, 0, 100, 50),
+    control(ReverbTime, REVERB_TIME_CONTROL, 3, 2, 0, 0xffff, 256),
+    control(ReverbFeedback, REVERB_FEEDBACK_CONTROL, 4, 1, 0, 255, 0),
+];
+
+const CHORUS_CONTROLS: [ControlInfo; 4] = [
+    ENABLE,
+    control(ChorusLevel, CHORUS_LEVEL_CONTROL, 1, 1, 0, 100, 50),
+    control(ChorusRate, CHORUS_RATE_CONTROL, 2, 2, 0, 0xffff, 256),
+    control(ChorusDepth, CHORUS_DEPTH_CONTROL, 3, 2, 0, 0xffff, 512),
+];
+
+const DRC_CONTROLS: [ControlInfo; 6] = [
+    ENABLE,
+    control(
+        CompressionRatio,
+        COMPRESSION_RATE_CONTROL,
+        1,
+        2,
+        0,
+        0xffff,
+        256,
+    ),
+    control(MaxAmplitude, MAXAMPL_CONTROL, 2, 2, -0x8000, 0x7fff, 0),
+    control(Threshold, THRESHOLD_CONTROL, 3, 2, -0x8000, 0x7fff, 0),
+    control(AttackTime, ATTACK_TIME, 4, 2, 0, 0xffff, 10 * 256),
+    control(ReleaseTime, RELEASE_TIME, 5, 2, 0, 0xffff, 100 * 256),
+];
+
+#[derive(Clone, Copy, Debug)]
+enum Process<'a> {
+    UpDownMix {
+        output_channels: u8,
+        modes: &'a [u16],
+    },
+    DolbyPrologic {
+        output_channels: u8,
+        modes: &'a [u16],
+    },
+    StereoExtender,
+    Reverb,
+    Chorus,
+    DynamicRangeCompressor,
+}
+
+/// Configuration of a Processing Unit placed in front of the Output Terminal
+/// of a stream or added to a `Topology`.
+///
+/// All controls of the Processing Unit are available to the host. The
+/// processing is initially disabled and the further controls have default
+/// values in the middle of their usual ranges.
+#[derive(Clone, Copy, Debug)]
+pub struct ProcessingUnitConfig<'a> {
+    process: Process<'a>,
+}
+
+impl<'a> ProcessingUnitConfig<'a> {
+    /// Up/Down-mix Processing Unit converting the input channels to
+    /// `output_channels` channels. `modes` are the wChannelConfig values of
+    /// the supported modes, i.e. the spatial locations of the output channels
+    /// that are used in a mode. Up to 8 modes are supported.
+    pub fn up_down_mix(output_channels: u8, modes: &'a [u16]) -> Self {
+        ProcessingUnitConfig {
+            process: Process::UpDownMix {
+                output_channels,
+                modes,
+            },
+        }
+    }
+
+    /// Dolby Prologic Processing Unit decoding two input channels to
+    /// `output_channels` channels. `modes` are the wChannelConfig values of
+    /// the supported modes, e.g. 0x0007 (L, R, C), 0x0103 (L, R, S) and 0x0107
+    /// (L, R, C, S). Up to 8 modes are supported.
+    pub fn dolby_prologic(output_channels: u8, modes: &'a [u16]) -> Self {
+        ProcessingUnitConfig {
+            process: Process::DolbyPrologic {
+                output_channels,
+                modes,
+            },
+        }
+    }
+
+    /// 3D Stereo Extender Processing Unit having two input channels and two
+    /// output channels
+    pub fn stereo_extender() -> Self {
+        ProcessingUnitConfig {
+            process: Process::StereoExtender,
+        }
+    }
+
+    /// Reverberation Processing Unit
+    pub fn reverb() -> Self {
+        ProcessingUnitConfig {
+            process: Process::Reverb,
+        }
+    }
+
+    /// Chorus Processing Unit
+    pub fn chorus() -> Self {
+        ProcessingUnitConfig {
+            process: Process::Chorus,
+        }
+    }
+
+    /// Dynamic Range Compressor Processing Unit
+    pub fn dynamic_range_compressor() -> Self {
+        ProcessingUnitConfig {
+            process: Process::DynamicRangeCompressor,
+        }
+    }
+
+    /// Number of output channels for `input_channels` input channels or
+    /// `None` if the number of input channels is not supported
+    pub(crate) fn output_channels(&self, input_channels: u8) -> Option<u8> {
+        match self.process {
+            Process::UpDownMix {
+                output_channels, ..
+            } => Some(output_channels),
+            Process::DolbyPrologic {
+                output_channels, ..
+            } => (input_channels == 2).then_some(output_channels),
+            Process::StereoExtender => (input_channels == 2).then_some(2),
+            _ => Some(input_channels),
+        }
+    }
+
+    fn process_type(&self) -> u16 {
+        match self.process {
+            Process::UpDownMix { .. } => UP_DOWNMIX_PROCESS,
+            Process::DolbyPrologic { .. } => DOLBY_PROLOGIC_PROCESS,
+            Process::StereoExtender => DDD_STEREO_EXTENDER_PROCESS,
+            Process::Reverb => REVERBERATION_PROCESS,
+            Process::Chorus => CHORUS_PROCESS,
+            Process::DynamicRangeCompressor => DYN_RANGE_COMP_PROCESS,
+        }
+    }
+
+    fn modes(&self) -> &'a [u16] {
+        match self.process {
+            Process::UpDownMix { modes, .. } | Process::DolbyPrologic { modes, .. } => modes,
+            _ => &[],
+        }
+    }
+
+    fn controls(&self) -> &'static [ControlInfo] {
+        match self.process {
+            Process::UpDownMix { .. } | Process::DolbyPrologic { .. } => &MODE_CONTROLS,
+            Process::StereoExtender => &DDD_CONTROLS,
+            Process::Reverb => &REVERB_CONTROLS,
+            Process::Chorus => &CHORUS_CONTROLS,
+            Process::DynamicRangeCompressor => &DRC_CONTROLS,
+        }
+    }
+}
+
+/// Processing Unit state including the current values of the controls
+pub(crate) struct ProcessingUnit {
+    pub(crate) id: u8,
+    source_id: u8,
+    process_type: u16,
+    controls: &'static [ControlInfo],
+    modes: [u16; MAX_MODES],
+    num_modes: u8,
+    cluster: Cluster,
+    values: [i32; MAX_CONTROLS],
+}
+
+impl ProcessingUnit {
+    /// Create a Processing Unit state processing the `input_channels`
+    /// channels of the Terminal or Unit `source_id`. `cluster` is the output
+    /// cluster. Returns `None` if the configuration is not valid for
+    /// `input_channels` input channels.
+    pub(crate) fn new(
+        config: &ProcessingUnitConfig,
+        id: u8,
+        source_id: u8,
+        input_channels: u8,
+        cluster: Cluster,
+    ) -> Option<ProcessingUnit> {
+        let output_channels = config.output_channels(input_channels)?;
+        if cluster.channels != output_channels {
+            return None;
+        }
+        let config_modes = config.modes();
+        let num_modes = config_modes.len();
+        let has_modes = matches!(
+            config.process,
+            Process::UpDownMix { .. } | Process::DolbyPrologic { .. }
+        );
+        if output_channels == 0 || num_modes > MAX_MODES || (has_modes && num_modes == 0) {
+            return None;
+        }
+        let mut modes = [0; MAX_MODES];
+        modes[..num_modes].copy_from_slice(config_modes);
+        let controls = config.controls();
+        let mut values = [0; MAX_CONTROLS];
+        for (value, info) in values.iter_mut().zip(controls) {
+            *value = info.default;
+        }
+        Some(ProcessingUnit {
+            id,
+            source_id,
+            process_type: config.process_type(),
+            controls,
+            modes,
+            num_modes: num_modes as u8,
+            cluster,
+            values,
+        })
+    }
+
+    /// Find a control by its Control Selector
+    fn control(&self, selector: u8) -> Option<(usize, &'static ControlInfo)> {
+        self.controls
+            .iter()
+            .enumerate()
+            .find(|(_, info)| info.selector == selector)
+    }
+
+    /// Upper limit of a control
+    fn max(&self, info: &ControlInfo) -> i32 {
+        if info.control == ModeSelect {
+            self.num_modes as i32
+        } else {
+            info.max
+        }
+    }
+
+    /// Current value of a control
+    pub(crate) fn value(&self, control: ProcessingControl) -> Option<i32> {
+        self.controls
+            .iter()
+            .position(|info| info.control == control)
+            .map(|ndx| self.values[ndx])
+    }
+
+    /// Handle a GET request. Returns the number of bytes written to `data` or
+    /// `None` if the request is not supported.
+    pub(crate) fn get(&self, request: u8, value: u16, data: &mut [u8; 2]) -> Option<usize> {
+        let (ndx, info) = self.control((value >> 8) as u8)?;
+        if value as u8 != 0 {
+            return None;
+        }
+        let value = match request {
+            GET_CUR => self.values[ndx],
+            _ if info.control == Enable => return None,
+            GET_MIN => info.min,
+            GET_MAX => self.max(info),
+            GET_RES => 1,
+            _ => return None,
+        };
+        data.copy_from_slice(&(value as u16).to_le_bytes());
+        Some(info.size as usize)
+    }
+
+    /// Handle a SET request. Returns `false` if the request is not supported
+    /// or the value is out of range.
+    pub(crate) fn set(&mut self, request: u8, value: u16, data: &[u8]) -> bool {
+        let Some((ndx, info)) = self.control((value >> 8) as u8) else {
+            return false;
+        };
+        if request != SET_CUR || value as u8 != 0 || data.len() < info.size as usize {
+            return false;
+        }
+        let value = match (info.size, info.min < 0) {
+            (1, _) => data[0] as i32,
+            (_, false) => u16::from_le_bytes([data[0], data[1]]) as i32,
+            (_, true) => i16::from_le_bytes([data[0], data[1]]) as i32,
+        };
+        if value < info.min || value > self.max(info) {
+            return false;
+        }
+        self.values[ndx] = value;
+        true
+    }
+}
+
+impl AcDescriptor for ProcessingUnit {
+    fn id(&self) -> u8 {
+        self.id
+    }
+
+    fn write_descriptor(&self, desc: &mut [u8], name: u8) -> usize {
+        let bm_controls = self
+            .controls
+            .iter()
+            .fold(0u8, |bm, info| bm | 1 << info.bit);
+        let [channels, config_lo, config_hi, names] = self.cluster.descriptor();
+        desc[..13].copy_from_slice(&[
+            PROCESSING_UNIT, // bDescriptorSubtype
+            self.id,         // bUnitID
+            self.process_type as u8,
+            (self.process_type >> 8) as u8, // wProcessType
+            0x01,                           // bNrInPins
+            self.source_id,                 // baSourceID
+            channels,                       // bNrChannels
+            config_lo,
+            config_hi,   // wChannelConfig
+            names,       // iChannelNames
+            0x01,        // bControlSize
+            bm_controls, // bmControls
+            name,        // iProcessing
+        ]);
+        let mut len = 13;
+        if self.num_modes > 0 {
+            desc[len] = self.num_modes; // bNrModes
+            len += 1;
+            for mode in &self.modes[..self.num_modes as usize] {
+                desc[len..len + 2].copy_from_slice(&mode.to_le_bytes()); // waModes
+                len += 2;
+            }
+        }
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bm_controls(config: ProcessingUnitConfig) -> u8 {
+        let unit = ProcessingUnit::new(&config, 2, 1, 2, Cluster::new(2)).unwrap();
+        let mut desc = [0; 32];
+        unit.write_descriptor(&mut desc, 0);
+        desc[11]
+    }
+
+    #[test]
+    fn bm_controls_follow_spec() {
+        assert_eq!(
+            bm_controls(ProcessingUnitConfig::up_down_mix(2, &[0x0003])),
+            0x03
+        );
+        assert_eq!(bm_controls(ProcessingUnitConfig::stereo_extender()), 0x03);
+        assert_eq!(bm_controls(ProcessingUnitConfig::reverb()), 0x1d);
+        assert_eq!(bm_controls(ProcessingUnitConfig::chorus()), 0x0f);
+        assert_eq!(
+            bm_controls(ProcessingUnitConfig::dynamic_range_compressor()),
+            0x3f
+        );
+    }
+}
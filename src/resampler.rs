@@ -0,0 +1,393 @@
+//! Asynchronous sample rate converter for adaptive and synchronous output
+//! streams
+//!
+//! The host sends the audio data of an adaptive or synchronous output stream
+//! at a rate derived from its own clock, which differs slightly from the clock
+//! of the DAC. The `Resampler` converts the audio frames read from the stream
+//! to the rate of the DAC. Its conversion ratio is steered by the fill level
+//! of the FIFO feeding the DAC such that the FIFO neither runs empty nor
+//! overflows.
+//!
+//! ```ignore
+//! static FIFO: AudioFifo<i16, 2, 512> = AudioFifo::new(0);
+//!
+//! let (mut producer, mut consumer) = FIFO.split().unwrap();
+//! let mut resampler = Resampler::<i16, 2>::new(Format::S16le, Quality::Polyphase, 48000, 48000)
+//!     .unwrap()
+//!     .with_fill_control(256, 16, 24);
+//! // USB task
+//! let mut frames = [[0i16; 2]; 49];
+//! if let Ok(len) = usb_audio.read_frames(&mut frames) {
+//!     resampler.process_fifo(&frames[..len], &mut producer);
+//! }
+//! // DMA half-buffer interrupt
+//! consumer.pop(&mut dma_half_buffer);
+//! ```
+//!
+//! The samples are processed as linear 32 bit values in fixed-point
+//! arithmetic. All formats of PCM audio data (Type I formats) are supported
+//! by the corresponding `Sample` type. Compressed audio data (Type II and
+//! Type III formats) cannot be resampled.
+//!
+
+use crate::class_codes::FORMAT_TYPE_I;
+#[cfg(target_has_atomic = "ptr")]
+use crate::fifo::Producer;
+use crate::{Error, Format, Result, Sample};
+use core::marker::PhantomData;
+
+/// Number of taps of the polyphase filter
+const TAPS: usize = 8;
+
+/// Number of phases of the polyphase filter table as power of two
+const PHASE_BITS: u32 = 5;
+
+/// Fixed-point one of the position of an output frame (32.32 format)
+const ONE: u64 = 1 << 32;
+
+/// Maximum deviation of the conversion ratio from the nominal ratio as power
+/// of two, i.e. ±1/64
+const MAX_DEVIATION_SHIFT: u32 = 6;
+
+/// Number of audio frames resampled at once by `Resampler::process_fifo`
+const CHUNK_FRAMES: usize = 32;
+
+/// Coefficients of the polyphase filter in 2.30 format. Row p holds the
+/// coefficients for an output frame p/32 frames after the fourth of eight
+/// input frames. The coefficients are derived from a sinc function with a
+/// cutoff frequency of 0.9 times the Nyquist frequency and a Blackman window.
+/// Each row is normalized to a DC gain of 1.
+#[rustfmt::skip]
+const COEFFICIENTS: [[i32; TAPS]; (1 << PHASE_BITS) + 1] = [
+    [6124507, -34153054, 81702358, 966394202, 81702358, -34153054, 6124507, 0],
+    [5254217, -28358948, 56457750, 964895738, 108640255, -40183932, 7041296, -4552],
+    [4437328, -22842271, 32964988, 960410932, 137197799, -46404320, 7996031, -18663],
+    [3679160, -17637095, 11267440, 952970530, 167286384, -52760331, 8978461, -42725],
+    [2983531, -12771046, -8606536, 942624843, 198802675, -59191523, 9976596, -76716],
+    [2352863, -8265508, -26643253, 929443240, 231629012, -65631051, 10976704, -120183],
+    [1788287, -4135890, -42843215, 913513493, 265633949, -72005898, 11963325, -172227],
+    [1289763, -391945, -57220523, 894940964, 300672942, -78237195, 12919314, -231496],
+    [856208, 2961880, -69802172, 873847622, 336589166, -84240622, 13825917, -296175],
+    [485618, 5926043, -80627251, 850370948, 373214474, -89926887, 14662876, -363997],
+    [175200, 8505492, -89746061, 824662715, 410370467, -95202303, 15408563, -432249],
+    [-78503, 10709223, -97219155, 796887624, 447869699, -99969428, 16040150, -497786],
+    [-279486, 12549818, -103116330, 767221886, 485516974, -104127797, 16533818, -557059],
+    [-432162, 14042993, -107515565, 735851685, 523110748, -107574717, 16864990, -606148],
+    [-541249, 15207122, -110501933, 702971599, 560444618, -110206140, 17008603, -640796],
+    [-611658, 16062789, -112166488, 668782952, 597308874, -111917591, 16939413, -656467],
+    [-648395, 16632332, -112605150, 633492125, 633492125, -112605150, 16632332, -648395],
+    [-656467, 16939413, -111917591, 597308874, 668782952, -112166488, 16062789, -611658],
+    [-640796, 17008603, -110206140, 560444618, 702971599, -110501933, 15207122, -541249],
+    [-606148, 16864990, -107574717, 523110748, 735851685, -107515565, 14042993, -432162],
+    [-557059, 16533818, -104127797, 485516974, 767221886, -103116330, 12549818, -279486],
+    [-497786, 16040150, -99969428, 447869699, 796887624, -97219155, 10709223, -78503],
+    [-432249, 15408563, -95202303, 410370467, 824662715, -89746061, 8505492, 175200],
+    [-363997, 14662876, -89926887, 373214474, 850370948, -80627251, 5926043, 485618],
+    [-296175, 13825917, -84240622, 336589166, 873847622, -69802172, 2961880, 856208],
+    [-231496, 12919314, -78237195, 300672942, 894940964, -57220523, -391945, 1289763],
+    [-172227, 11963325, -72005898, 265633949, 913513493, -42843215, -4135890, 1788287],
+    [-120183, 10976704, -65631051, 231629012, 929443240, -26643253, -8265508, 2352863],
+    [-76716, 9976596, -59191523, 198802675, 942624843, -8606536, -12771046, 2983531],
+    [-42725, 8978461, -52760331, 167286384, 952970530, 11267440, -17637095, 3679160],
+    [-18663, 7996031, -46404320, 137197799, 960410932, 32964988, -22842271, 4437328],
+    [-4552, 7041296, -40183932, 108640255, 964895738, 56457750, -28358948, 5254217],
+    [0, 6124507, -34153054, 81702358, 966394202, 81702358, -34153054, 6124507],
+];
+
+/// Interpolation method of a `Resampler`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Quality {
+    /// Linear interpolation between two input frames. Needs the least
+    /// processing time and delays the signal by two input frames.
+    Linear,
+    /// 8 tap polyphase filter suppressing the images of the input signal.
+    /// Delays the signal by five input frames.
+    Polyphase,
+}
+
+/// Fill level control of a `Resampler`
+#[derive(Clone, Copy, Debug)]
+struct FillControl {
+    target: usize,
+    p_shift: u32,
+    i_shift: u32,
+}
+
+/// Asynchronous sample rate converter for audio frames of `C` channels of
+/// samples of type `S`
+pub struct Resampler<S, const C: usize> {
+    format: Format,
+    quality: Quality,
+    /// Last input frames as linear samples
+    history: [[i32; C]; TAPS],
+    /// Index of the oldest frame in `history`
+    oldest: usize,
+    /// Position of the next output frame relative to the last but one input
+    /// frame (32.32 format)
+    position: u64,
+    /// Nominal ratio of input rate and output rate (32.32 format)
+    nominal_step: u64,
+    /// Current ratio of input rate and output rate (32.32 format)
+    step: u64,
+    fill_control: Option<FillControl>,
+    /// Integral of the fill level deviation
+    integral: i64,
+    _sample: PhantomData<S>,
+}
+
+impl<S: Sample, const C: usize> Resampler<S, C> {
+    /// Create a resampler converting audio frames of format `format` from
+    /// the sampling rate `input_rate` to the sampling rate `output_rate`. The
+    /// filter is designed for ratios close to 1 or upsampling. Returns an
+    /// error if a sampling rate is zero, `format` is not a Type I format or
+    /// `S` does not match `format`.
+    pub fn new(
+        format: Format,
+        quality: Quality,
+        input_rate: u32,
+        output_rate: u32,
+    ) -> Result<Resampler<S, C>> {
+        if input_rate == 0
+            || output_rate == 0
+            || format.format_type() != FORMAT_TYPE_I
+            || !S::supports(format)
+        {
+            return Err(Error::InvalidValue);
+        }
+        let nominal_step = ((input_rate as u64) << 32) / output_rate as u64;
+        Ok(Resampler {
+            format,
+            quality,
+            history: [[0; C]; TAPS],
+            oldest: 0,
+            position: 0,
+            nominal_step,
+            step: nominal_step,
+            fill_control: None,
+            integral: 0,
+            _sample: PhantomData,
+        })
+    }
+
+    /// Steer the conversion ratio by means of a PI controller such that the
+    /// fill level passed to `set_fill_level` approaches `target` frames. A
+    /// deviation of one frame changes the ratio by 2^-`p_shift` and the sum
+    /// of the deviations of all calls of `set_fill_level` changes it by
+    /// 2^-`i_shift` per frame. The shifts are limited to 32. The ratio
+    /// deviates from the nominal ratio by at most 1/64.
+    pub fn with_fill_control(self, target: usize, p_shift: u8, i_shift: u8) -> Resampler<S, C> {
+        Resampler {
+            fill_control: Some(FillControl {
+                target,
+                p_shift: p_shift.min(32) as u32,
+                i_shift: i_shift.min(32) as u32,
+            }),
+            ..self
+        }
+    }
+
+    /// Clear the input frames and restart the fill level control, e.g. when
+    /// the host selects an alternate setting of the stream
+    pub fn reset(&mut self) {
+        self.history = [[0; C]; TAPS];
+        self.oldest = 0;
+        self.position = 0;
+        self.step = self.nominal_step;
+        self.integral = 0;
+    }
+
+    /// Current ratio of input rate and output rate in 32.32 format
+    pub fn ratio(&self) -> u64 {
+        self.step
+    }
+
+    /// Pass the current fill level of the FIFO fed by the resampler in
+    /// frames, e.g. once per packet. Ignored unless the fill level control is
+    /// enabled.
+    pub fn set_fill_level(&mut self, fill: usize) {
+        let Some(control) = self.fill_control else {
+            return;
+        };
+        let max_deviation = (self.nominal_step >> MAX_DEVIATION_SHIFT) as i64;
+        let error = fill as i64 - control.target as i64;
+        // limit the integral to the range having an effect (anti-windup)
+        let max_integral = (max_deviation >> (32 - control.i_shift)).max(1);
+        self.integral = self
+            .integral
+            .saturating_add(error)
+            .clamp(-max_integral, max_integral);
+        let deviation =
+            scale(error, control.p_shift).saturating_add(scale(self.integral, control.i_shift));
+        let deviation = deviation.clamp(-max_deviation, max_deviation);
+        self.step = (self.nominal_step as i64 + deviation) as u64;
+    }
+
+    /// Resample the audio frames `input` and write the resulting audio frames
+    /// to `output`. Stops when `output` is full. Returns the number of input
+    /// frames consumed and the number of output frames written.
+    pub fn process(&mut self, input: &[[S; C]], output: &mut [[S; C]]) -> (usize, usize) {
+        let mut produced = 0;
+        for (consumed, frame) in input.iter().enumerate() {
+            // output frames are computed up to the position of the last input
+            // frame before the next input frame is accepted
+            while self.position < ONE {
+                let Some(out) = output.get_mut(produced) else {
+                    return (consumed, produced);
+                };
+                *out = self.interpolate();
+                produced += 1;
+                self.position += self.step;
+            }
+            self.position -= ONE;
+            let slot = &mut self.history[self.oldest];
+            for (linear, sample) in slot.iter_mut().zip(frame) {
+                *linear = sample.to_linear(self.format);
+            }
+            self.oldest = (self.oldest + 1) % TAPS;
+        }
+        (input.len(), produced)
+    }
+
+    /// Resample the audio frames `input` and append the resulting audio
+    /// frames to a FIFO. The fill level of the FIFO is passed to
+    /// `set_fill_level` before. Returns the number of output frames appended.
+    #[cfg(target_has_atomic = "ptr")]
+    pub fn process_fifo<const N: usize>(
+        &mut self,
+        input: &[[S; C]],
+        producer: &mut Producer<S, C, N>,
+    ) -> usize {
+        self.set_fill_level(producer.fifo().len());
+        let mut output = [[S::from_linear(0, self.format); C]; CHUNK_FRAMES];
+        let mut input = input;
+        let mut appended = 0;
+        loop {
+            let (consumed, produced) = self.process(input, &mut output);
+            appended += producer.push(&output[..produced]);
+            input = &input[consumed..];
+            if produced < CHUNK_FRAMES {
+                return appended;
+            }
+        }
+    }
+
+    /// Input frame `ndx` of the history, 0 being the oldest frame
+    fn input_frame(&self, ndx: usize) -> &[i32; C] {
+        &self.history[(self.oldest + ndx) % TAPS]
+    }
+
+    /// Compute the output frame at the current position
+    fn interpolate(&self) -> [S; C] {
+        let frac = self.position as u32;
+        core::array::from_fn(|ch| {
+            let value = match self.quality {
+                Quality::Linear => {
+                    let a = self.input_frame(TAPS - 2)[ch] as i64;
+                    let b = self.input_frame(TAPS - 1)[ch] as i64;
+                    // a full-scale difference takes 33 bits such that the
+                    // fraction is reduced to 31 bits to fit the product into
+                    // 64 bits
+                    a + (((b - a) * (frac >> 1) as i64) >> 31)
+                }
+                Quality::Polyphase => {
+                    let phase = (frac >> (32 - PHASE_BITS)) as usize;
+                    // position between the phases in 0.16 format
+                    let between = ((frac << PHASE_BITS) >> 16) as i64;
+                    let sum = (0..TAPS)
+                        .map(|tap| {
+                            let c0 = COEFFICIENTS[phase][tap] as i64;
+                            let c1 = COEFFICIENTS[phase + 1][tap] as i64;
+                            let coefficient = c0 + (((c1 - c0) * between) >> 16);
+                            self.input_frame(tap)[ch] as i64 * coefficient
+                        })
+                        .sum::<i64>();
+                    (sum + (1 << 29)) >> 30
+                }
+            };
+            S::from_linear(
+                value.clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+                self.format,
+            )
+        })
+    }
+}
+
+/// Convert a fill level deviation to a ratio deviation of 2^-`shift` per
+/// frame in 32.32 format
+fn scale(value: i64, shift: u32) -> i64 {
+    value.saturating_mul(1 << (32 - shift))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Nominal ratio of equal sampling rates
+    const UNITY: u64 = 1 << 32;
+
+    #[test]
+    fn dc_passthrough() {
+        for quality in [Quality::Linear, Quality::Polyphase] {
+            let mut resampler =
+                Resampler::<i16, 2>::new(Format::S16le, quality, 48000, 48000).unwrap();
+            let mut output = [[0; 2]; 48];
+            assert_eq!(
+                resampler.process(&[[1000, -32768]; 48], &mut output),
+                (48, 48)
+            );
+            // skip the frames affected by the initial history
+            for frame in &output[8..] {
+                assert_eq!(*frame, [1000, -32768], "{quality:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn full_scale() {
+        let mut input = [[0i32; 1]; 64];
+        for (ndx, frame) in input.iter_mut().enumerate() {
+            *frame = [if ndx % 2 == 0 { i32::MIN } else { i32::MAX }];
+        }
+        for quality in [Quality::Linear, Quality::Polyphase] {
+            let mut resampler =
+                Resampler::<i32, 1>::new(Format::S32le, quality, 48000, 44100).unwrap();
+            let mut output = [[0; 1]; 64];
+            let (consumed, produced) = resampler.process(&input, &mut output);
+            assert_eq!(consumed, input.len());
+            assert!((58..=60).contains(&produced), "{quality:?}: {produced}");
+        }
+        // the overshoot of the polyphase filter saturates
+        let mut resampler =
+            Resampler::<i32, 1>::new(Format::S24le, Quality::Polyphase, 48000, 48000).unwrap();
+        let mut input = [[-(1 << 23)]; 16];
+        input[8..].fill([(1 << 23) - 1]);
+        let mut output = [[0; 1]; 16];
+        resampler.process(&input, &mut output);
+        for frame in output {
+            assert!((-(1 << 23)..1 << 23).contains(&frame[0]));
+        }
+    }
+
+    #[test]
+    fn fill_control() {
+        let mut resampler = Resampler::<i16, 1>::new(Format::S16le, Quality::Linear, 48000, 48000)
+            .unwrap()
+            .with_fill_control(100, 16, 24);
+        resampler.set_fill_level(100);
+        assert_eq!(resampler.ratio(), UNITY);
+        // a FIFO that is too full is fed with less frames
+        resampler.set_fill_level(110);
+        assert!(resampler.ratio() > UNITY);
+        resampler.reset();
+        resampler.set_fill_level(90);
+        assert!(resampler.ratio() < UNITY);
+        // the ratio is limited to ±1/64
+        resampler.set_fill_level(usize::MAX / 2);
+        assert_eq!(resampler.ratio(), UNITY + (UNITY >> 6));
+        let mut resampler = Resampler::<i16, 1>::new(Format::S16le, Quality::Linear, 48000, 48000)
+            .unwrap()
+            .with_fill_control(usize::MAX / 2, 16, 24);
+        resampler.set_fill_level(0);
+        assert_eq!(resampler.ratio(), UNITY - (UNITY >> 6));
+    }
+}
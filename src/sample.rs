@@ -0,0 +1,229 @@
+//! Sample types carried in the subframes of Type I and Type III formats
+//!
+
+use crate::codec;
+use crate::Format;
+
+/// Full scale of a linear sample as floating point value
+const LINEAR_FULL_SCALE: f32 = 2147483648.0;
+
+/// Sample type that can be packed into and unpacked from the subframes of an
+/// audio stream by `AudioClass::write_frames` and `AudioClass::read_frames`.
+///
+/// The following sample types are supported:
+///
+/// - `i8` for `Format::Pcm` with 1 byte subframes
+/// - `u8` for `Format::U8`, `Format::ALaw` and `Format::MuLaw`. The samples
+///   are the encoded values, see the `codec` module.
+/// - `i16` for `Format::S16le`, `Format::Iec61937` and `Format::Pcm` with 2
+///   byte subframes
+/// - `i32` for `Format::S24le`, `Format::S32le` and `Format::Pcm` with 2 to 4
+///   byte subframes. The samples are right-justified, e.g. a sample of
+///   `Format::S24le` ranges from -2^23 to 2^23 - 1.
+/// - `f32` for `Format::F32le`
+pub trait Sample: Copy {
+    /// Check if the samples can be carried in the subframes of `format`
+    fn supports(format: Format) -> bool;
+
+    /// Pack the sample into `subframe` (little endian)
+    fn pack(self, subframe: &mut [u8]);
+
+    /// Unpack a sample from `subframe` (little endian)
+    fn unpack(subframe: &[u8]) -> Self;
+
+    /// Convert the sample of a stream of format `format` to a linear sample
+    /// ranging from -2^31 to 2^31 - 1
+    fn to_linear(self, format: Format) -> i32;
+
+    /// Convert a linear sample ranging from -2^31 to 2^31 - 1 to a sample of
+    /// a stream of format `format`
+    fn from_linear(value: i32, format: Format) -> Self;
+}
+
+impl Sample for i8 {
+    fn supports(format: Format) -> bool {
+        matches!(
+            format,
+            Format::Pcm {
+                subframe_bytes: 1,
+                ..
+            }
+        )
+    }
+
+    fn pack(self, subframe: &mut [u8]) {
+        subframe[0] = self as u8;
+    }
+
+    fn unpack(subframe: &[u8]) -> Self {
+        subframe[0] as i8
+    }
+
+    fn to_linear(self, _format: Format) -> i32 {
+        (self as i32) << 24
+    }
+
+    fn from_linear(value: i32, _format: Format) -> Self {
+        (value >> 24) as i8
+    }
+}
+
+impl Sample for u8 {
+    fn supports(format: Format) -> bool {
+        matches!(format, Format::U8 | Format::ALaw | Format::MuLaw)
+    }
+
+    fn pack(self, subframe: &mut [u8]) {
+        subframe[0] = self;
+    }
+
+    fn unpack(subframe: &[u8]) -> Self {
+        subframe[0]
+    }
+
+    fn to_linear(self, format: Format) -> i32 {
+        let sample = match format {
+            Format::ALaw => codec::alaw_decode(self),
+            Format::MuLaw => codec::mulaw_decode(self),
+            _ => codec::u8_decode(self),
+        };
+        (sample as i32) << 16
+    }
+
+    fn from_linear(value: i32, format: Format) -> Self {
+        let sample = (value >> 16) as i16;
+        match format {
+            Format::ALaw => codec::alaw_encode(sample),
+            Format::MuLaw => codec::mulaw_encode(sample),
+            _ => codec::u8_encode(sample),
+        }
+    }
+}
+
+impl Sample for i16 {
+    fn supports(format: Format) -> bool {
+        matches!(
+            format,
+            Format::S16le
+                | Format::Iec61937(_)
+                | Format::Pcm {
+                    subframe_bytes: 2,
+                    ..
+                }
+        )
+    }
+
+    fn pack(self, subframe: &mut [u8]) {
+        subframe.copy_from_slice(&self.to_le_bytes());
+    }
+
+    fn unpack(subframe: &[u8]) -> Self {
+        i16::from_le_bytes([subframe[0], subframe[1]])
+    }
+
+    fn to_linear(self, _format: Format) -> i32 {
+        (self as i32) << 16
+    }
+
+    fn from_linear(value: i32, _format: Format) -> Self {
+        (value >> 16) as i16
+    }
+}
+
+impl Sample for i32 {
+    fn supports(format: Format) -> bool {
+        matches!(
+            format,
+            Format::S24le
+                | Format::S32le
+                | Format::Pcm {
+                    subframe_bytes: 2..=4,
+                    ..
+                }
+        )
+    }
+
+    fn pack(self, subframe: &mut [u8]) {
+        let len = subframe.len();
+        subframe.copy_from_slice(&self.to_le_bytes()[..len]);
+    }
+
+    fn unpack(subframe: &[u8]) -> Self {
+        // place the subframe in the upper bytes and shift back to sign-extend
+        let shift = 4 - subframe.len();
+        let mut bytes = [0; 4];
+        bytes[shift..].copy_from_slice(subframe);
+        i32::from_le_bytes(bytes) >> (8 * shift)
+    }
+
+    fn to_linear(self, format: Format) -> i32 {
+        self << (32 - 8 * format.subframe_size() as u32)
+    }
+
+    fn from_linear(value: i32, format: Format) -> Self {
+        value >> (32 - 8 * format.subframe_size() as u32)
+    }
+}
+
+impl Sample for f32 {
+    fn supports(format: Format) -> bool {
+        matches!(format, Format::F32le)
+    }
+
+    fn pack(self, subframe: &mut [u8]) {
+        subframe.copy_from_slice(&self.to_le_bytes());
+    }
+
+    fn unpack(subframe: &[u8]) -> Self {
+        f32::from_le_bytes([subframe[0], subframe[1], subframe[2], subframe[3]])
+    }
+
+    fn to_linear(self, _format: Format) -> i32 {
+        // the conversion saturates
+        (self * LINEAR_FULL_SCALE) as i32
+    }
+
+    fn from_linear(value: i32, _format: Format) -> Self {
+        value as f32 / LINEAR_FULL_SCALE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_round_trip() {
+        let pcm8 = Format::Pcm {
+            subframe_bytes: 1,
+            bits: 8,
+        };
+        for sample in [i8::MIN, -1, 0, i8::MAX] {
+            assert_eq!(i8::from_linear(sample.to_linear(pcm8), pcm8), sample);
+        }
+        for sample in [i16::MIN, -1, 0, i16::MAX] {
+            let linear = sample.to_linear(Format::S16le);
+            assert_eq!(linear >> 16, sample as i32);
+            assert_eq!(i16::from_linear(linear, Format::S16le), sample);
+        }
+        for sample in [-(1 << 23), -1, 0, (1 << 23) - 1] {
+            let linear = sample.to_linear(Format::S24le);
+            assert_eq!(linear >> 8, sample);
+            assert_eq!(i32::from_linear(linear, Format::S24le), sample);
+        }
+        for sample in [i32::MIN, i32::MAX] {
+            assert_eq!(
+                i32::from_linear(sample.to_linear(Format::S32le), Format::S32le),
+                sample
+            );
+        }
+        for format in [Format::U8, Format::ALaw, Format::MuLaw] {
+            // A-law has no code for zero
+            let silence = u8::from_linear(0, format);
+            assert!((silence.to_linear(format) >> 16).abs() <= 8, "{format:?}");
+        }
+        assert_eq!(0.5f32.to_linear(Format::F32le), 1 << 30);
+        assert_eq!(2.0f32.to_linear(Format::F32le), i32::MAX);
+        assert_eq!(f32::from_linear(i32::MIN, Format::F32le), -1.0);
+    }
+}
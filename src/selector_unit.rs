@@ -0,0 +1,96 @@
+//! Selector Unit with a host controlled selection of one of several inputs
+//!
+
+use crate::class_codes::*;
+use crate::topology::AcDescriptor;
+
+/// Maximum number of Input Pins of a Selector Unit
+pub(crate) const MAX_INPUT_PINS: usize = 8;
+
+/// Selector Unit state including the currently selected Input Pin
+pub(crate) struct SelectorUnit {
+    pub(crate) id: u8,
+    num_pins: u8,
+    source_ids: [u8; MAX_INPUT_PINS],
+    current: u8,
+    changed: bool,
+}
+
+impl SelectorUnit {
+    /// Create a Selector Unit state with Input Pin 1 being selected.
+    /// `source_ids` are the IDs of the Terminals or Units connected to the
+    /// Input Pins. Returns `None` if the number of Input Pins is not valid.
+    pub(crate) fn new(id: u8, source_ids: &[u8]) -> Option<SelectorUnit> {
+        if source_ids.is_empty() || source_ids.len() > MAX_INPUT_PINS {
+            return None;
+        }
+        let mut su = SelectorUnit {
+            id,
+            num_pins: source_ids.len() as u8,
+            source_ids: [0; MAX_INPUT_PINS],
+            current: 1,
+            changed: false,
+        };
+        su.source_ids[..source_ids.len()].copy_from_slice(source_ids);
+        Some(su)
+    }
+
+    /// Currently selected Input Pin (1, 2, ...)
+    pub(crate) fn current(&self) -> u8 {
+        self.current
+    }
+
+    /// Check if the host has changed the selected Input Pin since the last
+    /// call of this method
+    pub(crate) fn take_changed(&mut self) -> bool {
+        core::mem::take(&mut self.changed)
+    }
+
+    /// Handle a GET request. Returns the value of the Selector Control or
+    /// `None` if the request is not supported.
+    pub(crate) fn get(&self, request: u8, value: u16) -> Option<u8> {
+        if value != 0 {
+            return None;
+        }
+        match request {
+            GET_CUR => Some(self.current),
+            GET_MIN | GET_RES => Some(1),
+            GET_MAX => Some(self.num_pins),
+            _ => None,
+        }
+    }
+
+    /// Handle a SET request. Returns `false` if the request is not supported
+    /// or the Input Pin does not exist.
+    pub(crate) fn set(&mut self, request: u8, value: u16, data: &[u8]) -> bool {
+        if request != SET_CUR || value != 0 {
+            return false;
+        }
+        match data.first() {
+            Some(&pin) if (1..=self.num_pins).contains(&pin) => {
+                if pin != self.current {
+                    self.current = pin;
+                    self.changed = true;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl AcDescriptor for SelectorUnit {
+    fn id(&self) -> u8 {
+        self.id
+    }
+
+    fn write_descriptor(&self, desc: &mut [u8], name: u8) -> usize {
+        desc[0] = SELECTOR_UNIT; // bDescriptorSubtype
+        desc[1] = self.id; // bUnitID
+        desc[2] = self.num_pins; // bNrInPins
+        let len = 3 + self.num_pins as usize + 1;
+        desc[3..len - 1].copy_from_slice(&self.source_ids[..self.num_pins as usize]); // baSourceID
+        desc[len - 1] = name; // iSelector
+        len
+    }
+}
@@ -4,6 +4,9 @@
 
 /// USB Audio Terminal Types from "Universal Serial Bus Device Class Definition
 /// for Terminal Types, Release 1.0"
+///
+/// External Terminal Types, such as `ExtSpdifConnector`, can be used for input
+/// streams as well as for output streams.
 #[rustfmt::skip]
 #[repr(u16)]
 #[non_exhaustive]
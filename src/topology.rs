@@ -0,0 +1,933 @@
+//! Topology of the Terminals and Units of the audio function
+//!
+
+use crate::class_codes::*;
+use crate::cluster::Cluster;
+use crate::extension_unit::{self, ExtensionUnit, ExtensionUnitConfig};
+use crate::feature_unit::{FeatureUnit, FeatureUnitConfig};
+use crate::mixer_unit::{self, MixerConfig, MixerSource, MixerUnit};
+use crate::processing_unit::{ProcessingUnit, ProcessingUnitConfig, MAX_PROCESSING_UNITS};
+use crate::selector_unit::{self, SelectorUnit};
+use crate::{push, Error, Result, StreamConfig, TerminalType};
+use usb_device::class_prelude::*;
+use usb_device::UsbDirection;
+
+/// Maximum number of Terminals and Units of a `Topology`
+pub const MAX_ENTITIES: usize = 32;
+
+/// Maximum number of Input Pins of a Unit
+const MAX_SOURCES: usize = selector_unit::MAX_INPUT_PINS;
+
+/// Maximum number of Terminals of a topology
+const MAX_TERMINALS: usize = 24;
+
+/// Maximum length of a Terminal or Unit Descriptor without the bLength and
+/// bDescriptorType fields
+const MAX_DESCRIPTOR_LENGTH: usize = 64;
+
+/// Terminal or Unit having a class-specific AudioControl Interface Descriptor
+pub(crate) trait AcDescriptor {
+    /// ID of the Terminal or Unit
+    fn id(&self) -> u8;
+
+    /// Write the descriptor starting with the bDescriptorSubtype field to
+    /// `desc`. `name` is the string index of the name of the Terminal or
+    /// Unit. Returns the number of bytes written.
+    fn write_descriptor(&self, desc: &mut [u8], name: u8) -> usize;
+}
+
+/// Handle of a Terminal or Unit added to a `Topology`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Entity(u8);
+
+impl Entity {
+    /// ID of the Terminal or Unit (bTerminalID or bUnitID). The IDs are
+    /// assigned in the order in which the Terminals and Units are added to
+    /// the `Topology` starting with 1.
+    pub fn id(self) -> u8 {
+        self.0 + 1
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kind<'a> {
+    /// Input Terminal with its output cluster
+    InputTerminal(TerminalType, Cluster),
+    OutputTerminal(TerminalType),
+    /// USB streaming Input Terminal of an output stream
+    StreamingInput(usize),
+    /// USB streaming Output Terminal of an input stream
+    StreamingOutput(usize),
+    SelectorUnit,
+    FeatureUnit(FeatureUnitConfig),
+    /// Mixer Unit with a number of output channels
+    MixerUnit(MixerConfig<'a>, u8),
+    ProcessingUnit(ProcessingUnitConfig<'a>),
+    /// Extension Unit with a number of output channels
+    ExtensionUnit(ExtensionUnitConfig<'a>, u8),
+}
+
+impl Kind<'_> {
+    /// Maximum number of Input Pins
+    fn max_sources(&self) -> usize {
+        match self {
+            Kind::InputTerminal(..) | Kind::StreamingInput(_) => 0,
+            Kind::SelectorUnit => selector_unit::MAX_INPUT_PINS,
+            Kind::MixerUnit(..) => mixer_unit::MAX_INPUT_PINS,
+            Kind::ExtensionUnit(..) => extension_unit::MAX_INPUT_PINS,
+            _ => 1,
+        }
+    }
+
+    /// Check if the Terminal or Unit provides a signal to other Units
+    fn has_output(&self) -> bool {
+        !matches!(self, Kind::OutputTerminal(_) | Kind::StreamingOutput(_))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Node<'a> {
+    kind: Kind<'a>,
+    sources: [Entity; MAX_SOURCES],
+    num_sources: u8,
+    name: Option<&'a str>,
+}
+
+impl Node<'_> {
+    fn sources(&self) -> &[Entity] {
+        &self.sources[..self.num_sources as usize]
+    }
+}
+
+/// Topology of the Terminals and Units of the audio function.
+///
+/// The Terminals and Units are added together with the Terminals or Units
+/// connected to their Input Pins (sources). Further Input Pins of Selector,
+/// Mixer and Extension Units can be connected later by means of `connect`.
+/// The topology is passed to `AudioClassBuilder::topology`, which checks that
+/// it has no cycles, that the numbers of channels match and that each stream
+/// has exactly one USB streaming Terminal. The streams then have no Units
+/// configured by their `StreamConfig`.
+///
+/// A topology supports up to `MAX_ENTITIES` Terminals and Units. Thereof, up
+/// to 24 Terminals are supported. The `AudioClass` keeps the states of up to
+/// the number of Units reserved by `AudioClassBuilder::max_units`.
+///
+/// Example of a stereo microphone with a Feature Unit and a side tone mixed
+/// into the playback signal:
+///
+/// ```ignore
+/// let mut topology = Topology::new();
+/// let mic = topology.input_terminal(TerminalType::InMicrophone, 2)?;
+/// let fu = topology.feature_unit(FeatureUnitConfig::new().master_volume(), mic)?;
+/// topology.streaming_output(0, fu)?;
+/// let playback = topology.streaming_input(0)?;
+/// let mixer = topology.mixer_unit(MixerConfig::new(&[]), &[playback, fu], 2)?;
+/// topology.output_terminal(TerminalType::OutHeadphones, mixer)?;
+/// ```
+#[derive(Debug)]
+pub struct Topology<'a> {
+    nodes: [Option<Node<'a>>; MAX_ENTITIES],
+    len: u8,
+}
+
+impl Default for Topology<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Topology<'a> {
+    /// Create an empty topology
+    pub fn new() -> Topology<'a> {
+        Topology {
+            nodes: [None; MAX_ENTITIES],
+            len: 0,
+        }
+    }
+
+    fn node(&self, entity: Entity) -> Result<&Node<'a>> {
+        self.nodes
+            .get(entity.index())
+            .and_then(Option::as_ref)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Check that `source` exists and can be connected to an Input Pin
+    fn check_source(&self, source: Entity) -> Result<()> {
+        if self.node(source)?.kind.has_output() {
+            Ok(())
+        } else {
+            Err(Error::InvalidValue)
+        }
+    }
+
+    fn add(&mut self, kind: Kind<'a>, sources: &[Entity]) -> Result<Entity> {
+        let ndx = self.len as usize;
+        if ndx >= MAX_ENTITIES || sources.len() > kind.max_sources() {
+            return Err(Error::InvalidValue);
+        }
+        for &source in sources {
+            self.check_source(source)?;
+        }
+        let mut node = Node {
+            kind,
+            sources: [Entity(0); MAX_SOURCES],
+            num_sources: sources.len() as u8,
+            name: None,
+        };
+        node.sources[..sources.len()].copy_from_slice(sources);
+        self.nodes[ndx] = Some(node);
+        self.len += 1;
+        Ok(Entity(ndx as u8))
+    }
+
+    /// Add an Input Terminal, e.g. a microphone, providing `channels`
+    /// channels
+    pub fn input_terminal(&mut self, terminal_type: TerminalType, channels: u8) -> Result<Entity> {
+        if channels == 0 {
+            return Err(Error::InvalidValue);
+        }
+        self.add(
+            Kind::InputTerminal(terminal_type, Cluster::new(channels)),
+            &[],
+        )
+    }
+
+    /// Add an Output Terminal, e.g. a speaker, connected to `source`
+    pub fn output_terminal(
+        &mut self,
+        terminal_type: TerminalType,
+        source: Entity,
+    ) -> Result<Entity> {
+        self.add(Kind::OutputTerminal(terminal_type), &[source])
+    }
+
+    /// Add the USB streaming Input Terminal of the output stream `stream`.
+    /// It provides the channels of the stream as output by the host.
+    pub fn streaming_input(&mut self, stream: usize) -> Result<Entity> {
+        self.add(Kind::StreamingInput(stream), &[])
+    }
+
+    /// Add the USB streaming Output Terminal of the input stream `stream`
+    /// connected to `source`. The number of channels of `source` must match
+    /// the stream.
+    pub fn streaming_output(&mut self, stream: usize, source: Entity) -> Result<Entity> {
+        self.add(Kind::StreamingOutput(stream), &[source])
+    }
+
+    /// Add a Selector Unit selecting one of the signals `sources` having the
+    /// same number of channels
+    pub fn selector_unit(&mut self, sources: &[Entity]) -> Result<Entity> {
+        self.add(Kind::SelectorUnit, sources)
+    }
+
+    /// Add a Feature Unit processing the signal of `source`
+    pub fn feature_unit(&mut self, config: FeatureUnitConfig, source: Entity) -> Result<Entity> {
+        self.add(Kind::FeatureUnit(config), &[source])
+    }
+
+    /// Add a Mixer Unit mixing the signals `sources` into `channels` output
+    /// channels. The configuration must not have stream sources; all Input
+    /// Pins are connected by `sources` or `connect`.
+    pub fn mixer_unit(
+        &mut self,
+        config: MixerConfig<'a>,
+        sources: &[Entity],
+        channels: u8,
+    ) -> Result<Entity> {
+        if !config.sources().is_empty() {
+            return Err(Error::InvalidValue);
+        }
+        self.add(Kind::MixerUnit(config, channels), sources)
+    }
+
+    /// Add a Processing Unit processing the signal of `source`
+    pub fn processing_unit(
+        &mut self,
+        config: ProcessingUnitConfig<'a>,
+        source: Entity,
+    ) -> Result<Entity> {
+        self.add(Kind::ProcessingUnit(config), &[source])
+    }
+
+    /// Add an Extension Unit processing the signals `sources` into
+    /// `channels` output channels. The configuration must not have stream
+    /// sources; all Input Pins are connected by `sources` or `connect`.
+    pub fn extension_unit(
+        &mut self,
+        config: ExtensionUnitConfig<'a>,
+        sources: &[Entity],
+        channels: u8,
+    ) -> Result<Entity> {
+        if !config.source_streams().is_empty() {
+            return Err(Error::InvalidValue);
+        }
+        self.add(Kind::ExtensionUnit(config, channels), sources)
+    }
+
+    /// Name a Terminal or Unit. The name is provided as string descriptor
+    /// (iTerminal, iSelector, iFeature, iMixer, iProcessing or iExtension).
+    pub fn set_name(&mut self, entity: Entity, name: &'a str) -> Result<()> {
+        let node = self
+            .nodes
+            .get_mut(entity.index())
+            .and_then(Option::as_mut)
+            .ok_or(Error::InvalidValue)?;
+        node.name = Some(name);
+        Ok(())
+    }
+
+    /// IDs of the named Terminals and Units
+    pub(crate) fn names(&self) -> impl Iterator<Item = u8> + '_ {
+        self.nodes.iter().enumerate().filter_map(|(ndx, node)| {
+            node.as_ref()?.name?;
+            Some(Entity(ndx as u8).id())
+        })
+    }
+
+    /// Name of the Terminal or Unit `id`
+    pub(crate) fn name(&self, id: u8) -> Option<&'a str> {
+        let ndx = id.checked_sub(1)?;
+        self.node(Entity(ndx)).ok()?.name
+    }
+
+    /// Connect a further Input Pin of the Selector, Mixer or Extension Unit
+    /// `unit` to `source`. Unlike the sources passed when adding a Unit,
+    /// `source` may be added to the topology after `unit`.
+    pub fn connect(&mut self, unit: Entity, source: Entity) -> Result<()> {
+        self.check_source(source)?;
+        let node = self
+            .nodes
+            .get_mut(unit.index())
+            .and_then(Option::as_mut)
+            .ok_or(Error::InvalidValue)?;
+        let num_sources = node.num_sources as usize;
+        let multiple_pins = matches!(
+            node.kind,
+            Kind::SelectorUnit | Kind::MixerUnit(..) | Kind::ExtensionUnit(..)
+        );
+        if !multiple_pins || num_sources >= node.kind.max_sources() {
+            return Err(Error::InvalidValue);
+        }
+        node.sources[num_sources] = source;
+        node.num_sources += 1;
+        Ok(())
+    }
+
+    /// Find the USB streaming Terminal of a stream. Returns an error if the
+    /// stream has none or more than one.
+    pub(crate) fn streaming_terminal(
+        &self,
+        direction: UsbDirection,
+        stream: usize,
+    ) -> Result<Entity> {
+        let mut terminals = self.nodes.iter().flatten().enumerate().filter(|(_, node)| {
+            match (direction, node.kind) {
+                (UsbDirection::In, Kind::StreamingOutput(n)) => n == stream,
+                (UsbDirection::Out, Kind::StreamingInput(n)) => n == stream,
+                _ => false,
+            }
+        });
+        match (terminals.next(), terminals.next()) {
+            (Some((ndx, _)), None) => Ok(Entity(ndx as u8)),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Add the chain of Terminals and Units configured by the `StreamConfig`
+    /// of the stream `stream`: Input Terminal, further Input Terminals,
+    /// Selector Unit, Extension Unit, Feature Unit, Mixer Unit, Processing
+    /// Units and Output Terminal. The stream sources of the Mixer Unit and the
+    /// Extension Unit are connected by `connect_stream_sources`. The Input
+    /// Terminals of an input stream provide the cluster `cluster`.
+    pub(crate) fn add_stream(
+        &mut self,
+        config: &StreamConfig<'a>,
+        direction: UsbDirection,
+        stream: usize,
+        cluster: Cluster,
+    ) -> Result<StreamUnits> {
+        let mut units = StreamUnits::default();
+        let mut signal = match direction {
+            UsbDirection::In if cluster.channels == 0 => return Err(Error::InvalidValue),
+            UsbDirection::In => {
+                self.add(Kind::InputTerminal(config.terminal_type, cluster), &[])?
+            }
+            UsbDirection::Out => self.streaming_input(stream)?,
+        };
+        let input_terminal = signal;
+        let num_selector_inputs = config.selector_inputs.len();
+        if num_selector_inputs > 0 {
+            let mut pins = [signal; MAX_SOURCES];
+            if num_selector_inputs >= pins.len() {
+                return Err(Error::InvalidValue);
+            }
+            for (pin, terminal_type) in pins[1..].iter_mut().zip(config.selector_inputs) {
+                *pin = self.add(Kind::InputTerminal(*terminal_type, cluster), &[])?;
+            }
+            signal = self.selector_unit(&pins[..=num_selector_inputs])?;
+            units.selector_unit = Some(signal);
+        }
+        if let Some(xu) = config.extension_unit {
+            signal = self.add(Kind::ExtensionUnit(xu, config.channels), &[signal])?;
+            units.extension_unit = Some(signal);
+        }
+        if let Some(fu) = config.feature_unit {
+            signal = self.feature_unit(fu, signal)?;
+            units.feature_unit = Some(signal);
+        }
+        if let Some(mixer) = config.mixer {
+            signal = self.add(Kind::MixerUnit(mixer, config.channels), &[signal])?;
+            units.mixer_unit = Some(signal);
+        }
+        if config.processing_units.len() > MAX_PROCESSING_UNITS {
+            return Err(Error::InvalidValue);
+        }
+        for (unit, pu) in units
+            .processing_units
+            .iter_mut()
+            .zip(config.processing_units)
+        {
+            signal = self.processing_unit(*pu, signal)?;
+            *unit = Some(signal);
+        }
+        units.signal = Some(signal);
+        let terminal = match direction {
+            UsbDirection::In => {
+                self.streaming_output(stream, signal)?;
+                input_terminal
+            }
+            UsbDirection::Out => self.output_terminal(config.terminal_type, signal)?,
+        };
+        if let Some(name) = config.terminal_name {
+            self.set_name(terminal, name)?;
+        }
+        units.terminal = Some(terminal);
+        if let Some(names) = config.unit_names {
+            let processing_names = names.processing_units.iter().map(|&name| Some(name));
+            let named = [
+                (units.selector_unit, names.selector_unit),
+                (units.extension_unit, names.extension_unit),
+                (units.feature_unit, names.feature_unit),
+                (units.mixer_unit, names.mixer_unit),
+            ]
+            .into_iter()
+            .chain(units.processing_units.into_iter().zip(processing_names));
+            for (unit, name) in named {
+                if let (Some(unit), Some(name)) = (unit, name) {
+                    self.set_name(unit, name)?;
+                }
+            }
+        }
+        Ok(units)
+    }
+
+    /// Connect the further Input Pins of the Mixer Unit and the Extension
+    /// Unit of a stream to the signals of the streams configured by its
+    /// `StreamConfig`. `inputs` and `outputs` are the chains of the input and
+    /// output streams.
+    pub(crate) fn connect_stream_sources(
+        &mut self,
+        config: &StreamConfig<'a>,
+        units: &StreamUnits,
+        inputs: &[StreamUnits],
+        outputs: &[StreamUnits],
+    ) -> Result<()> {
+        let signal = |source: &MixerSource| {
+            match *source {
+                MixerSource::InputStream(stream) => inputs.get(stream),
+                MixerSource::OutputStream(stream) => outputs.get(stream),
+            }
+            .and_then(|units| units.signal)
+            .ok_or(Error::InvalidValue)
+        };
+        if let Some(mixer_unit) = units.mixer_unit {
+            for source in config.mixer_sources() {
+                self.connect(mixer_unit, signal(source)?)?;
+            }
+        }
+        if let Some(extension_unit) = units.extension_unit {
+            for source in config.extension_unit_sources() {
+                self.connect(extension_unit, signal(source)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the topology and create the states of its Terminals and Units.
+    /// `inputs` and `outputs` are the clusters of the input and output
+    /// streams. Returns an error if the topology has cycles, if the
+    /// numbers of channels do not match, if a stream does not have exactly
+    /// one USB streaming Terminal or if it has more than `N` Units.
+    pub(crate) fn build<const N: usize>(
+        &self,
+        inputs: &[Cluster],
+        outputs: &[Cluster],
+    ) -> Result<Entities<N>> {
+        for (direction, num_streams) in [
+            (UsbDirection::In, inputs.len()),
+            (UsbDirection::Out, outputs.len()),
+        ] {
+            for stream in 0..num_streams {
+                self.streaming_terminal(direction, stream)?;
+            }
+        }
+        let mut entities = Entities::new(self.len);
+        // output clusters of the Terminals and Units created so far
+        let mut clusters: [Option<Cluster>; MAX_ENTITIES] = [None; MAX_ENTITIES];
+        let mut remaining = self.len as usize;
+        while remaining > 0 {
+            let mut progress = false;
+            for (ndx, node) in self.nodes.iter().enumerate() {
+                let Some(node) = node else {
+                    continue;
+                };
+                if clusters[ndx].is_some() {
+                    continue;
+                }
+                // wait until all sources have been created
+                if node
+                    .sources()
+                    .iter()
+                    .any(|source| clusters[source.index()].is_none())
+                {
+                    continue;
+                }
+                let mut sources = [(0, Cluster::default()); MAX_SOURCES];
+                for (source, entity) in sources.iter_mut().zip(node.sources()) {
+                    *source = (entity.id(), clusters[entity.index()].unwrap_or_default());
+                }
+                let id = Entity(ndx as u8).id();
+                let sources = &sources[..node.sources().len()];
+                clusters[ndx] = Some(entities.add(id, &node.kind, sources, inputs, outputs)?);
+                remaining -= 1;
+                progress = true;
+            }
+            if !progress {
+                // the remaining Terminals and Units are part of a cycle
+                return Err(Error::InvalidValue);
+            }
+        }
+        Ok(entities)
+    }
+}
+
+/// Units of the chain of Terminals and Units of a stream configured by its
+/// `StreamConfig`
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct StreamUnits {
+    /// Input Terminal of an input stream or Output Terminal of an output
+    /// stream
+    pub(crate) terminal: Option<Entity>,
+    pub(crate) selector_unit: Option<Entity>,
+    pub(crate) extension_unit: Option<Entity>,
+    pub(crate) feature_unit: Option<Entity>,
+    pub(crate) mixer_unit: Option<Entity>,
+    pub(crate) processing_units: [Option<Entity>; MAX_PROCESSING_UNITS],
+    /// Terminal or Unit connected to the Output Terminal
+    signal: Option<Entity>,
+}
+
+/// Terminal state
+enum Terminal {
+    Input {
+        id: u8,
+        terminal_type: TerminalType,
+        cluster: Cluster,
+    },
+    Output {
+        id: u8,
+        terminal_type: TerminalType,
+        source_id: u8,
+    },
+}
+
+impl AcDescriptor for Terminal {
+    fn id(&self) -> u8 {
+        match *self {
+            Terminal::Input { id, .. } | Terminal::Output { id, .. } => id,
+        }
+    }
+
+    fn write_descriptor(&self, desc: &mut [u8], name: u8) -> usize {
+        match *self {
+            Terminal::Input {
+                id,
+                terminal_type,
+                cluster,
+            } => {
+                let tt = u16::from(terminal_type).to_le_bytes();
+                let [channels, config_lo, config_hi, names] = cluster.descriptor();
+                desc[..10].copy_from_slice(&[
+                    INPUT_TERMINAL, // bDescriptorSubtype
+                    id,             // bTerminalID
+                    tt[0],          // wTerminalType
+                    tt[1],
+                    0x00,     // bAssocTerminal
+                    channels, // bNrChannels
+                    config_lo,
+                    config_hi, // wChannelConfig
+                    names,     // iChannelNames
+                    name,      // iTerminal
+                ]);
+                10
+            }
+            Terminal::Output {
+                id,
+                terminal_type,
+                source_id,
+            } => {
+                let tt = u16::from(terminal_type).to_le_bytes();
+                desc[..7].copy_from_slice(&[
+                    OUTPUT_TERMINAL, // bDescriptorSubtype
+                    id,              // bTerminalID
+                    tt[0],           // wTerminalType
+                    tt[1],
+                    0x00,      // bAssocTerminal
+                    source_id, // bSourceID
+                    name,      // iTerminal
+                ]);
+                7
+            }
+        }
+    }
+}
+
+/// Unit state. A slot for a Unit has the size of the largest Unit, which
+/// is acceptable as only the slots reserved by `AudioClassBuilder::max_units`
+/// exist.
+#[allow(clippy::large_enum_variant)]
+enum Unit {
+    Selector(SelectorUnit),
+    Feature(FeatureUnit),
+    Mixer(MixerUnit),
+    Processing(ProcessingUnit),
+    Extension(ExtensionUnit),
+}
+
+impl Unit {
+    fn descriptor(&self) -> &dyn AcDescriptor {
+        match self {
+            Unit::Selector(su) => su,
+            Unit::Feature(fu) => fu,
+            Unit::Mixer(mu) => mu,
+            Unit::Processing(pu) => pu,
+            Unit::Extension(xu) => xu,
+        }
+    }
+}
+
+/// States of the Terminals and Units of a topology. Room for `N` Units is
+/// reserved such that devices without Units do not pay for them.
+pub(crate) struct Entities<const N: usize> {
+    num_entities: u8,
+    /// String indices of the names of the Terminals and Units by ID - 1
+    names: [u8; MAX_ENTITIES],
+    terminals: [Option<Terminal>; MAX_TERMINALS],
+    units: [Option<Unit>; N],
+}
+
+impl<const N: usize> Entities<N> {
+    fn new(num_entities: u8) -> Entities<N> {
+        Entities {
+            num_entities,
+            names: [0; MAX_ENTITIES],
+            terminals: Default::default(),
+            units: core::array::from_fn(|_| None),
+        }
+    }
+
+    /// Create the state of a Terminal or Unit. `sources` are the pairs of ID
+    /// and output cluster of the Terminals or Units connected to the Input
+    /// Pins. Returns the output cluster.
+    fn add(
+        &mut self,
+        id: u8,
+        kind: &Kind,
+        sources: &[(u8, Cluster)],
+        inputs: &[Cluster],
+        outputs: &[Cluster],
+    ) -> Result<Cluster> {
+        let mut source_ids = [0; MAX_SOURCES];
+        let mut source_channels = [(0, 0); MAX_SOURCES];
+        for ((source_id, channels), &(id, cluster)) in source_ids
+            .iter_mut()
+            .zip(source_channels.iter_mut())
+            .zip(sources)
+        {
+            *source_id = id;
+            *channels = (id, cluster.channels);
+        }
+        let source_ids = &source_ids[..sources.len()];
+        let (source_id, source) = sources.first().copied().unwrap_or_default();
+        let added = match *kind {
+            Kind::InputTerminal(terminal_type, cluster) => push(
+                &mut self.terminals,
+                Terminal::Input {
+                    id,
+                    terminal_type,
+                    cluster,
+                },
+            )
+            .then_some(cluster),
+            Kind::StreamingInput(stream) => {
+                let cluster = *outputs.get(stream).ok_or(Error::InvalidValue)?;
+                push(
+                    &mut self.terminals,
+                    Terminal::Input {
+                        id,
+                        terminal_type: TerminalType::UsbStreaming,
+                        cluster,
+                    },
+                )
+                .then_some(cluster)
+            }
+            Kind::OutputTerminal(terminal_type) => push(
+                &mut self.terminals,
+                Terminal::Output {
+                    id,
+                    terminal_type,
+                    source_id,
+                },
+            )
+            .then_some(Cluster::default()),
+            Kind::StreamingOutput(stream) => {
+                if inputs.get(stream).map(|cluster| cluster.channels) != Some(source.channels) {
+                    return Err(Error::InvalidValue);
+                }
+                push(
+                    &mut self.terminals,
+                    Terminal::Output {
+                        id,
+                        terminal_type: TerminalType::UsbStreaming,
+                        source_id,
+                    },
+                )
+                .then_some(Cluster::default())
+            }
+            Kind::SelectorUnit => {
+                if sources
+                    .iter()
+                    .any(|(_, cluster)| cluster.channels != source.channels)
+                {
+                    return Err(Error::InvalidValue);
+                }
+                let su = SelectorUnit::new(id, source_ids).ok_or(Error::InvalidValue)?;
+                push(&mut self.units, Unit::Selector(su)).then_some(source)
+            }
+            Kind::FeatureUnit(config) => {
+                let fu = FeatureUnit::new(config, id, source_id, source.channels)
+                    .ok_or(Error::InvalidValue)?;
+                push(&mut self.units, Unit::Feature(fu)).then_some(source)
+            }
+            Kind::MixerUnit(ref config, channels) => {
+                let cluster = source.output(channels);
+                let sources = &source_channels[..sources.len()];
+                let mu = MixerUnit::new(config, id, sources, cluster).ok_or(Error::InvalidValue)?;
+                push(&mut self.units, Unit::Mixer(mu)).then_some(cluster)
+            }
+            Kind::ProcessingUnit(ref config) => {
+                let channels = config
+                    .output_channels(source.channels)
+                    .ok_or(Error::InvalidValue)?;
+                let cluster = source.output(channels);
+                let pu = ProcessingUnit::new(config, id, source_id, source.channels, cluster)
+                    .ok_or(Error::InvalidValue)?;
+                push(&mut self.units, Unit::Processing(pu)).then_some(cluster)
+            }
+            Kind::ExtensionUnit(ref config, channels) => {
+                let cluster = source.output(channels);
+                let xu = ExtensionUnit::new(config, id, source_ids, cluster)
+                    .ok_or(Error::InvalidValue)?;
+                push(&mut self.units, Unit::Extension(xu)).then_some(cluster)
+            }
+        };
+        added.ok_or(Error::InvalidValue)
+    }
+
+    /// Find a Unit by its ID
+    fn unit(&self, id: u8) -> Option<&Unit> {
+        self.units
+            .iter()
+            .flatten()
+            .find(|unit| unit.descriptor().id() == id)
+    }
+
+    /// Find a Unit by its ID
+    fn unit_mut(&mut self, id: u8) -> Option<&mut Unit> {
+        self.units
+            .iter_mut()
+            .flatten()
+            .find(|unit| unit.descriptor().id() == id)
+    }
+
+    /// Find a Terminal or Unit by its ID
+    fn entity(&self, id: u8) -> Option<&dyn AcDescriptor> {
+        let terminal = self.terminals.iter().flatten().find(|t| t.id() == id);
+        match terminal {
+            Some(terminal) => Some(terminal),
+            None => self.unit(id).map(Unit::descriptor),
+        }
+    }
+
+    /// Iterate over the pairs of Terminal or Unit and the string index of its
+    /// name in the order of their IDs
+    fn iter(&self) -> impl Iterator<Item = (&dyn AcDescriptor, u8)> {
+        (1..=self.num_entities)
+            .filter_map(|id| Some((self.entity(id)?, self.names[id as usize - 1])))
+    }
+
+    /// Total length of the Terminal and Unit Descriptors as written by
+    /// `write_descriptors`
+    pub(crate) fn descriptors_length(&self) -> u16 {
+        let mut desc = [0u8; MAX_DESCRIPTOR_LENGTH];
+        self.iter()
+            .map(|(entity, name)| 2 + entity.write_descriptor(&mut desc, name) as u16)
+            .sum()
+    }
+
+    /// Write the Terminal and Unit Descriptors
+    pub(crate) fn write_descriptors(
+        &self,
+        writer: &mut DescriptorWriter,
+    ) -> usb_device::Result<()> {
+        let mut desc = [0u8; MAX_DESCRIPTOR_LENGTH];
+        for (entity, name) in self.iter() {
+            let len = entity.write_descriptor(&mut desc, name);
+            writer.write(CS_INTERFACE, &desc[..len])?;
+        }
+        Ok(())
+    }
+
+    /// Set the string index `index` of the name of the Terminal or Unit `id`
+    pub(crate) fn set_name(&mut self, id: u8, index: u8) {
+        if let Some(slot) = self.names.get_mut(id as usize - 1) {
+            *slot = index;
+        }
+    }
+
+    /// ID of the Terminal or Unit whose name has the string index `index`
+    pub(crate) fn named(&self, index: u8) -> Option<u8> {
+        let ndx = self.names.iter().position(|&i| i != 0 && i == index)?;
+        Some(ndx as u8 + 1)
+    }
+
+    /// Check if a Terminal or Unit with the ID `id` exists
+    pub(crate) fn contains(&self, id: u8) -> bool {
+        (1..=self.num_entities).contains(&id)
+    }
+
+    /// Handle a GET request to a control of a Feature Unit, Selector Unit,
+    /// Mixer Unit or Processing Unit. Returns the number of bytes written to
+    /// `data` or `None` if the request is not supported.
+    pub(crate) fn get_control(
+        &self,
+        id: u8,
+        request: u8,
+        value: u16,
+        data: &mut [u8],
+    ) -> Option<usize> {
+        let mut buf = [0u8; 2];
+        let len = if let Some(fu) = self.feature_unit(id) {
+            fu.get(request, value, &mut buf)
+        } else if let Some(su) = self.selector_unit(id) {
+            su.get(request, value).map(|pin| {
+                buf[0] = pin;
+                1
+            })
+        } else if let Some(mu) = self.mixer_unit(id) {
+            mu.get(request, value, &mut buf)
+        } else if let Some(pu) = self.processing_unit(id) {
+            pu.get(request, value, &mut buf)
+        } else {
+            None
+        }?;
+        data[..len].copy_from_slice(&buf[..len]);
+        Some(len)
+    }
+
+    /// Handle a SET request to a control of a Feature Unit, Selector Unit,
+    /// Mixer Unit or Processing Unit. Returns `false` if the request is not
+    /// supported.
+    pub(crate) fn set_control(&mut self, id: u8, request: u8, value: u16, data: &[u8]) -> bool {
+        if let Some(fu) = self.feature_unit_mut(id) {
+            fu.set(request, value, data)
+        } else if let Some(su) = self.selector_unit_mut(id) {
+            su.set(request, value, data)
+        } else if let Some(mu) = self.mixer_unit_mut(id) {
+            mu.set(request, value, data)
+        } else if let Some(pu) = self.processing_unit_mut(id) {
+            pu.set(request, value, data)
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn selector_unit(&self, id: u8) -> Option<&SelectorUnit> {
+        match self.unit(id)? {
+            Unit::Selector(su) => Some(su),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn selector_unit_mut(&mut self, id: u8) -> Option<&mut SelectorUnit> {
+        match self.unit_mut(id)? {
+            Unit::Selector(su) => Some(su),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn feature_unit(&self, id: u8) -> Option<&FeatureUnit> {
+        match self.unit(id)? {
+            Unit::Feature(fu) => Some(fu),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn feature_unit_mut(&mut self, id: u8) -> Option<&mut FeatureUnit> {
+        match self.unit_mut(id)? {
+            Unit::Feature(fu) => Some(fu),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn mixer_unit(&self, id: u8) -> Option<&MixerUnit> {
+        match self.unit(id)? {
+            Unit::Mixer(mu) => Some(mu),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn mixer_unit_mut(&mut self, id: u8) -> Option<&mut MixerUnit> {
+        match self.unit_mut(id)? {
+            Unit::Mixer(mu) => Some(mu),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn processing_unit(&self, id: u8) -> Option<&ProcessingUnit> {
+        match self.unit(id)? {
+            Unit::Processing(pu) => Some(pu),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn processing_unit_mut(&mut self, id: u8) -> Option<&mut ProcessingUnit> {
+        match self.unit_mut(id)? {
+            Unit::Processing(pu) => Some(pu),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn extension_unit(&self, id: u8) -> Option<&ExtensionUnit> {
+        match self.unit(id)? {
+            Unit::Extension(xu) => Some(xu),
+            _ => None,
+        }
+    }
+}